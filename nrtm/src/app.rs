@@ -0,0 +1,204 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context as _;
+
+/// Directories Neovim uses for an NVIM_APPNAME, which are named after it
+#[derive(Clone, Copy, Debug)]
+pub enum DirKind {
+    Config,
+    Data,
+    State,
+    Cache,
+}
+
+impl DirKind {
+    const ALL: [DirKind; 4] = [
+        DirKind::Config,
+        DirKind::Data,
+        DirKind::State,
+        DirKind::Cache,
+    ];
+
+    /// Directory for `appname`, following `stdpath()` of Neovim
+    pub fn dir(self, appname: &str) -> anyhow::Result<PathBuf> {
+        use DirKind::*;
+        let (var, unix_default) = match self {
+            Config => ("XDG_CONFIG_HOME", ".config"),
+            Data => ("XDG_DATA_HOME", ".local/share"),
+            State => ("XDG_STATE_HOME", ".local/state"),
+            Cache => ("XDG_CACHE_HOME", ".cache"),
+        };
+        if let Some(base) = env::var_os(var).filter(|v| !v.is_empty()) {
+            return Ok(PathBuf::from(base).join(appname));
+        }
+
+        if cfg!(windows) {
+            let local =
+                env::var_os("LOCALAPPDATA").context("LOCALAPPDATA is not set.")?;
+            let local = PathBuf::from(local);
+            Ok(match self {
+                Config => local.join(appname),
+                Data | State => local.join(format!("{appname}-data")),
+                Cache => local.join("Temp").join(appname),
+            })
+        } else {
+            let home = env::var_os("HOME").context("HOME is not set.")?;
+            Ok(PathBuf::from(home).join(unix_default).join(appname))
+        }
+    }
+}
+
+/// Check that `name` can be used as NVIM_APPNAME and as a directory name
+pub fn validate_name(name: &str) -> anyhow::Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        anyhow::bail!(
+            "Invalid app name {name:?}. Use letters, digits, `-`, `_`, and `.` only."
+        );
+    }
+    Ok(())
+}
+
+/// Whether `dir` looks like a Neovim config
+fn is_config(dir: &Path) -> bool {
+    ["init.lua", "init.vim", "lua"]
+        .iter()
+        .any(|name| dir.join(name).exists())
+}
+
+/// Names of Neovim configs in the config directory
+pub fn list() -> anyhow::Result<Vec<String>> {
+    let config_dir = DirKind::Config.dir("")?;
+    let mut names = Vec::new();
+    let Ok(entries) = config_dir.read_dir() else {
+        return Ok(names);
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if !is_config(&path) {
+            continue;
+        }
+        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Whether the config of `name` exists
+pub fn exists(name: &str) -> anyhow::Result<bool> {
+    Ok(DirKind::Config.dir(name)?.exists())
+}
+
+/// Create a config for `name`, copied from a directory or cloned from a Git
+/// repository if `from` is given
+pub fn new(name: &str, from: Option<&str>) -> anyhow::Result<()> {
+    validate_name(name)?;
+    let config_dir = DirKind::Config.dir(name)?;
+    if config_dir.exists() {
+        anyhow::bail!("{} already exists.", config_dir.display());
+    }
+
+    match from {
+        Some(from) if Path::new(from).is_dir() => {
+            copy_dir(Path::new(from), &config_dir)?;
+        }
+        Some(url) => {
+            let status = Command::new("git")
+                // A URL starting with `-` is not an option.
+                .args(["clone", "--", url])
+                .arg(&config_dir)
+                .status()
+                .context("Failed to run git")?;
+            if !status.success() {
+                anyhow::bail!("Failed to clone {url}.");
+            }
+        }
+        None => {
+            fs::create_dir_all(&config_dir)?;
+            fs::write(config_dir.join("init.lua"), "")?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy the config of `from` into a new config `to`
+pub fn clone(from: &str, to: &str) -> anyhow::Result<()> {
+    validate_name(to)?;
+    let from_dir = DirKind::Config.dir(from)?;
+    if !from_dir.exists() {
+        anyhow::bail!("{} does not exist.", from_dir.display());
+    }
+    let to_dir = DirKind::Config.dir(to)?;
+    if to_dir.exists() {
+        anyhow::bail!("{} already exists.", to_dir.display());
+    }
+    copy_dir(&from_dir, &to_dir)
+}
+
+/// Existing config, data, state, and cache directories of `name`, which [`remove`]
+/// removes
+pub fn dirs_to_remove(name: &str) -> anyhow::Result<Vec<PathBuf>> {
+    validate_name(name)?;
+    if name == "nvim" {
+        anyhow::bail!("The default config cannot be removed.");
+    }
+    let mut dirs = Vec::new();
+    for kind in DirKind::ALL {
+        let dir = kind.dir(name)?;
+        // Data and state share a directory on Windows.
+        if dir.exists() && !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    Ok(dirs)
+}
+
+/// Remove the config, data, state, and cache directories of `name`, returning the
+/// removed ones
+pub fn remove(name: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let dirs = dirs_to_remove(name)?;
+    for dir in &dirs {
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(dirs)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in from.read_dir()? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, &target)?;
+            #[cfg(not(unix))]
+            fs::copy(entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn validate_name_t() {
+    for name in ["nvim", "lazy-vim", "test_config", "v0.9"] {
+        assert!(validate_name(name).is_ok(), "{name}");
+    }
+    for name in ["", ".", "..", "../evil", "a/b", "a\\b", ".hidden", "a b"] {
+        assert!(validate_name(name).is_err(), "{name}");
+    }
+}
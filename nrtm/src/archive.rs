@@ -0,0 +1,373 @@
+#[cfg(target_os = "linux")]
+use std::process::{Command, Stdio};
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+use anyhow::Context as _;
+use flate2::read::GzDecoder;
+use indicatif::MultiProgress;
+use xz2::read::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+use crate::github::AssetType;
+
+/// Extract `archive` into `target`, dropping the top-level directory of its entries.
+///
+/// Entries that would end up outside of `target`, such as `../x`, absolute paths, and
+/// links pointing outside of it, are rejected. Messages are printed with the bars of
+/// `progress` suspended.
+pub fn extract(
+    archive: &Path,
+    archive_type: &AssetType,
+    target: &Path,
+    progress: &MultiProgress,
+) -> anyhow::Result<()> {
+    fs::create_dir_all(target)?;
+    let target = &target.canonicalize()?;
+
+    match archive_type {
+        AssetType::Zip => extract_zip(File::open(archive)?, target, progress)?,
+        AssetType::TarGz => {
+            extract_tar(GzDecoder::new(File::open(archive)?), target, progress)?
+        }
+        AssetType::TarXz => {
+            extract_tar(XzDecoder::new(File::open(archive)?), target, progress)?
+        }
+        AssetType::TarZst => {
+            extract_tar(ZstdDecoder::new(File::open(archive)?)?, target, progress)?
+        }
+        AssetType::AppImage => extract_appimage(archive, target)?,
+    }
+    check_links(target, target)
+}
+
+/// Check that every symlink in `dir` resolves to an existing path inside `target`.
+///
+/// A link may escape through another link extracted after it, such as `c -> x/b/..`
+/// followed by `x/b -> ..`, so links are checked only once all are in place.
+fn check_links(dir: &Path, target: &Path) -> anyhow::Result<()> {
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            if !path.canonicalize().is_ok_and(|p| p.starts_with(target)) {
+                let rel_path = path.strip_prefix(target).unwrap_or(&path);
+                anyhow::bail!("Unsafe link in archive: {}", rel_path.display());
+            }
+        } else if file_type.is_dir() {
+            check_links(&path, target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Extract an AppImage by running it with `--appimage-extract`, which works without
+/// FUSE.
+///
+/// The image unpacks itself, so its links are only checked by [`check_links`]
+/// afterwards like those of other archives.
+#[cfg(target_os = "linux")]
+fn extract_appimage(archive: &Path, target: &Path) -> anyhow::Result<()> {
+    fs::set_permissions(archive, fs::Permissions::from_mode(0o755))?;
+    let status = Command::new(archive.canonicalize()?)
+        .arg("--appimage-extract")
+        .current_dir(target)
+        .stdout(Stdio::null())
+        .status()
+        .with_context(|| format!("Failed to run {}", archive.display()))?;
+    if !status.success() {
+        anyhow::bail!("Failed to extract {}", archive.display());
+    }
+
+    // Neovim is installed into `usr` of the image.
+    let root = target.join("squashfs-root");
+    for entry in root.join("usr").read_dir()? {
+        let entry = entry?;
+        fs::rename(entry.path(), target.join(entry.file_name()))?;
+    }
+    fs::remove_dir_all(root)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn extract_appimage(archive: &Path, _target: &Path) -> anyhow::Result<()> {
+    anyhow::bail!("AppImage is only supported on Linux: {}", archive.display());
+}
+
+fn extract_zip(
+    archive: impl Read + io::Seek,
+    target: &Path,
+    progress: &MultiProgress,
+) -> anyhow::Result<()> {
+    let mut archive = zip::ZipArchive::new(archive)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let Some(rel_path) = relative_path(Path::new(file.name()))? else {
+            continue;
+        };
+        let path = prepare(target, &rel_path)?;
+
+        let mode = file.unix_mode();
+        if file.is_dir() {
+            fs::create_dir_all(&path)?;
+        } else if mode.is_some_and(|mode| mode & 0o170000 == 0o120000) {
+            let mut link = String::new();
+            file.read_to_string(&mut link)?;
+            create_symlink(&rel_path, Path::new(&link), &path, progress)?;
+        } else {
+            let mut outfile = File::create(&path)?;
+            io::copy(&mut file, &mut outfile)?;
+
+            #[cfg(unix)]
+            if let Some(mode) = mode {
+                fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn extract_tar(
+    archive: impl Read,
+    target: &Path,
+    progress: &MultiProgress,
+) -> anyhow::Result<()> {
+    use tar::EntryType::*;
+
+    let mut archive = tar::Archive::new(archive);
+
+    for file in archive.entries()? {
+        let mut file = file?;
+        let Some(rel_path) = relative_path(&file.path()?)? else {
+            continue;
+        };
+        let path = prepare(target, &rel_path)?;
+
+        match file.header().entry_type() {
+            Regular | Continuous | Directory => {
+                file.unpack(&path)?;
+            }
+            Symlink => {
+                let link = file.link_name()?.context("Missing link name")?;
+                create_symlink(&rel_path, &link, &path, progress)?;
+            }
+            Link => {
+                let link = file.link_name()?.context("Missing link name")?;
+                let source = relative_path(&link)?
+                    .map(|rel_path| target.join(rel_path))
+                    .filter(|source| {
+                        source.canonicalize().is_ok_and(|s| s.starts_with(target))
+                    })
+                    .with_context(|| {
+                        format!("Invalid hard link in archive: {}", link.display())
+                    })?;
+                fs::hard_link(source, &path)?;
+            }
+            // Devices, FIFOs, and metadata entries are not needed for Neovim.
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Path of an entry relative to the target, without the top-level directory.
+///
+/// Returns `None` for the top-level directory itself.
+fn relative_path(path: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => components.push(c),
+            Component::CurDir => {}
+            _ => anyhow::bail!("Unsafe path in archive: {}", path.display()),
+        }
+    }
+    if components.len() < 2 {
+        return Ok(None);
+    }
+    Ok(Some(components[1..].iter().collect()))
+}
+
+/// Create the parent directories of an entry and return the path to write it to.
+///
+/// Fails if a symlink extracted earlier would lead the entry outside of `target`.
+fn prepare(target: &Path, rel_path: &Path) -> anyhow::Result<PathBuf> {
+    let mut dir = target.to_path_buf();
+    for component in rel_path.parent().into_iter().flatten() {
+        dir.push(component);
+        match fs::symlink_metadata(&dir) {
+            Ok(metadata) if metadata.is_symlink() => {
+                if !dir.canonicalize()?.starts_with(target) {
+                    anyhow::bail!("Unsafe path in archive: {}", rel_path.display());
+                }
+            }
+            Ok(_) => {}
+            Err(_) => fs::create_dir(&dir)?,
+        }
+    }
+
+    // Replace an existing file or link rather than write through it.
+    let path = target.join(rel_path);
+    if fs::symlink_metadata(&path).is_ok_and(|m| !m.is_dir()) {
+        fs::remove_file(&path)?;
+    }
+    Ok(path)
+}
+
+/// Create a symlink at `path` for the entry at `rel_path`, checking lexically that
+/// `link` points inside of the target.
+///
+/// Links that escape only through another link are found by [`check_links`] once all
+/// entries are extracted.
+#[cfg_attr(unix, allow(unused_variables))]
+fn create_symlink(
+    rel_path: &Path,
+    link: &Path,
+    path: &Path,
+    progress: &MultiProgress,
+) -> anyhow::Result<()> {
+    let mut depth = rel_path.components().count() - 1;
+    for component in link.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => anyhow::bail!(
+                "Unsafe link in archive: {} -> {}",
+                rel_path.display(),
+                link.display()
+            ),
+        }
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(link, path)?;
+    #[cfg(not(unix))]
+    progress.suspend(|| eprintln!("Skip the symlink {}", path.display()));
+
+    Ok(())
+}
+
+#[cfg(test)]
+fn tar_gz(entries: &[(&str, tar::EntryType, &str)]) -> Vec<u8> {
+    let encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+    let mut builder = tar::Builder::new(encoder);
+    for (path, entry_type, data) in entries {
+        let mut header = tar::Header::new_old();
+        // Bypass the validation of `set_path` to build malicious archives.
+        header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+        header.set_entry_type(*entry_type);
+        header.set_mode(0o755);
+        let mut content = data.as_bytes();
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            header.set_link_name(data).unwrap();
+            content = &[];
+        }
+        header.set_size(content.len() as u64);
+        header.set_cksum();
+        builder.append(&header, content).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+#[test]
+fn extract_tar_t() {
+    use tar::EntryType::*;
+
+    let dir = tempfile::tempdir().unwrap();
+    let extract = |entries: &[(&str, tar::EntryType, &str)]| {
+        let archive = dir.path().join("nvim.tar.gz");
+        fs::write(&archive, tar_gz(entries)).unwrap();
+        let target = dir.path().join("out");
+        let result =
+            extract(&archive, &AssetType::TarGz, &target, &MultiProgress::new());
+        fs::remove_dir_all(target).unwrap();
+        result
+    };
+
+    extract(&[
+        ("nvim", Directory, ""),
+        ("nvim/bin/nvim", Regular, "nvim"),
+        ("nvim/bin/nvim2", Link, "nvim/bin/nvim"),
+        ("nvim/lib/nvim", Symlink, "../bin/nvim"),
+        ("nvim/lib/bin", Symlink, "../bin"),
+        ("nvim/lib/bin/nvim3", Regular, "nvim"),
+    ])
+    .unwrap();
+
+    let malicious = [
+        ("nvim/../../evil", Regular, "evil"),
+        ("/tmp/evil", Regular, "evil"),
+        ("nvim/evil", Symlink, "/tmp"),
+        ("nvim/evil", Symlink, "../.."),
+        ("nvim/evil", Link, "/etc/passwd"),
+        ("nvim/evil", Link, "nvim/../../etc/passwd"),
+    ];
+    for entry in malicious {
+        assert!(extract(&[entry]).is_err(), "{entry:?}");
+    }
+    // Links that escape only through another link, in either order, which are
+    // skipped on Windows
+    #[cfg(unix)]
+    {
+        assert!(
+            extract(&[("nvim/c", Symlink, "x/b/.."), ("nvim/x/b", Symlink, "..")])
+                .is_err()
+        );
+        assert!(
+            extract(&[("nvim/a/b", Symlink, ".."), ("nvim/c", Symlink, "a/b/..")])
+                .is_err()
+        );
+        assert!(extract(&[
+            ("nvim/a/b", Symlink, ".."),
+            ("nvim/c", Symlink, "a/b/.."),
+            ("nvim/c/evil", Regular, "evil"),
+        ])
+        .is_err());
+    }
+    assert!(!dir.path().join("evil").exists());
+}
+
+#[test]
+fn extract_zip_t() {
+    use zip::write::FileOptions;
+
+    let dir = tempfile::tempdir().unwrap();
+    let extract = |entries: &[(&str, Option<&str>)]| {
+        let archive = dir.path().join("nvim.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&archive).unwrap());
+        for (path, link) in entries {
+            if let Some(link) = link {
+                writer
+                    .add_symlink(*path, *link, FileOptions::default())
+                    .unwrap();
+            } else {
+                writer.start_file(*path, FileOptions::default()).unwrap();
+            }
+        }
+        writer.finish().unwrap();
+        let target = dir.path().join("out");
+        let result = extract(&archive, &AssetType::Zip, &target, &MultiProgress::new());
+        fs::remove_dir_all(target).unwrap();
+        result
+    };
+
+    assert!(
+        extract(&[("nvim/bin/nvim", None), ("nvim/nvim", Some("bin/nvim"))]).is_ok()
+    );
+    assert!(extract(&[("nvim/../evil", None)]).is_err());
+    assert!(extract(&[("nvim/evil", Some("../../evil"))]).is_err());
+    #[cfg(unix)]
+    assert!(extract(&[("nvim/c", Some("x/b/..")), ("nvim/x/b", Some(".."))]).is_err());
+    assert!(!dir.path().join("evil").exists());
+}
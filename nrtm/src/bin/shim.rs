@@ -1,32 +1,18 @@
-use std::{
-    env,
-    process::{exit, Command},
-};
+use std::env;
 
-use anyhow::Context as _;
-
-use nrtm::shim::State;
+use nrtm::shim::{self, State};
 
 fn main() -> anyhow::Result<()> {
-    let state = State::read()?;
+    let mut state = State::resolve(&env::current_dir()?)?;
     State::draft_to_current()?;
 
-    let exe_path = state.exe_path.context("Neovim is not installed.")?;
-    let mut command = Command::new(exe_path);
-
-    let appname = match env::var("NVIM_APPNAME") {
-        Ok(appname) => Some(appname),
-        Err(_) => state.appname,
-    };
-    if let Some(appname) = appname {
-        command.env("NVIM_APPNAME", appname);
-    }
-
-    let exit_code = command.args(env::args_os().skip(1)).status()?.code();
-
-    if let Some(code) = exit_code {
-        exit(code);
+    let mut args = env::args_os().skip(1).peekable();
+    if let Some(arg) = args.peek() {
+        if state.set_version_from_arg(arg)? {
+            args.next();
+        }
     }
 
-    Ok(())
+    let tool = shim::invoked_name();
+    Err(shim::exec(state.tool_command(&tool)?.args(args)))
 }
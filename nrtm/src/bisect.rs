@@ -0,0 +1,117 @@
+/// Result of testing an item
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Good,
+    Bad,
+    /// The item cannot be tested
+    Skip,
+}
+
+impl Outcome {
+    /// Interpret an exit code of a test command in the same way as `git bisect run`
+    pub fn from_exit_code(code: Option<i32>) -> Self {
+        match code {
+            Some(0) => Outcome::Good,
+            Some(125) => Outcome::Skip,
+            _ => Outcome::Bad,
+        }
+    }
+}
+
+/// Binary search for the first bad item in a list whose first item is good and last
+/// item is bad.
+pub struct Bisect<T> {
+    items: Vec<T>,
+    good: usize,
+    bad: usize,
+    /// Indices of items that cannot be tested
+    skipped: Vec<usize>,
+}
+
+impl<T> Bisect<T> {
+    pub fn new(items: Vec<T>) -> anyhow::Result<Self> {
+        if items.len() < 2 {
+            anyhow::bail!("At least two items are required to bisect.");
+        }
+        let bad = items.len() - 1;
+        Ok(Bisect {
+            items,
+            good: 0,
+            bad,
+            skipped: Vec::new(),
+        })
+    }
+
+    /// Indices of the items left to test
+    fn untested(&self) -> impl Iterator<Item = usize> + '_ {
+        (self.good + 1..self.bad).filter(|i| !self.skipped.contains(i))
+    }
+
+    /// The untested item closest to the middle, avoiding skipped ones
+    fn candidate(&self) -> Option<usize> {
+        let middle = (self.good + self.bad) / 2;
+        self.untested().min_by_key(|i| i.abs_diff(middle))
+    }
+
+    /// The next item to test, or `None` if no item is left to test
+    pub fn next(&self) -> Option<&T> {
+        self.candidate().map(|i| &self.items[i])
+    }
+
+    /// Record the result of testing the item returned by [`Bisect::next`]
+    pub fn mark(&mut self, outcome: Outcome) {
+        let Some(i) = self.candidate() else {
+            return;
+        };
+        match outcome {
+            Outcome::Good => self.good = i,
+            Outcome::Bad => self.bad = i,
+            Outcome::Skip => self.skipped.push(i),
+        }
+    }
+
+    /// Approximate number of tests left
+    pub fn steps_left(&self) -> u32 {
+        (self.untested().count() + 1).ilog2()
+    }
+
+    pub fn first_bad(&self) -> &T {
+        &self.items[self.bad]
+    }
+
+    /// The last good item
+    pub fn last_good(&self) -> &T {
+        &self.items[self.good]
+    }
+
+    /// Skipped items between the last good and the first bad item, any of which may
+    /// be the first bad one instead
+    pub fn skipped(&self) -> Vec<&T> {
+        (self.good + 1..self.bad)
+            .filter(|i| self.skipped.contains(i))
+            .map(|i| &self.items[i])
+            .collect()
+    }
+}
+
+#[test]
+fn bisect_t() {
+    for first_bad in 1..10 {
+        let mut bisect = Bisect::new((0..10).collect()).unwrap();
+        while let Some(&i) = bisect.next() {
+            let outcome = match i {
+                3 => Outcome::Skip,
+                i if i < first_bad => Outcome::Good,
+                _ => Outcome::Bad,
+            };
+            bisect.mark(outcome);
+        }
+        // 3 cannot be told apart from 4 without testing it.
+        let (expected, skipped) = match first_bad {
+            3 | 4 => (4, vec![&3]),
+            _ => (first_bad, vec![]),
+        };
+        assert_eq!(*bisect.first_bad(), expected, "{first_bad}");
+        assert_eq!(bisect.skipped(), skipped, "{first_bad}");
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context as _;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+use crate::{checksum, CACHE_DIR};
+
+static DOWNLOAD_DIR: Lazy<PathBuf> = Lazy::new(|| CACHE_DIR.join("downloads"));
+
+/// Information about a downloaded file, stored next to it
+#[derive(Serialize, Deserialize)]
+struct Meta {
+    url: String,
+    size: u64,
+    sha256: String,
+}
+
+fn meta_path(path: &Path) -> PathBuf {
+    path.with_file_name("meta.json")
+}
+
+/// Path to download `url` into, which is unique to the URL
+pub fn download_path(url: &str, file_name: &str) -> PathBuf {
+    let key = format!("{:x}", Sha256::digest(url));
+    DOWNLOAD_DIR.join(&key[..16]).join(file_name)
+}
+
+/// Check whether a file downloaded before can be used instead of downloading it again.
+///
+/// The file must match `sha256` if it is known, or otherwise the hash recorded when
+/// the download finished.
+pub fn is_reusable(path: &Path, sha256: Option<&str>) -> bool {
+    let Ok(content) = fs::read_to_string(meta_path(path)) else {
+        return false;
+    };
+    let Ok(meta) = serde_json::from_str::<Meta>(&content) else {
+        return false;
+    };
+    if fs::metadata(path).ok().map(|m| m.len()) != Some(meta.size) {
+        return false;
+    }
+    let Ok(actual) = checksum::sha256_file(path) else {
+        return false;
+    };
+    actual == sha256.unwrap_or(&meta.sha256)
+}
+
+/// Record that `url` has been downloaded into `path` completely
+pub fn record(path: &Path, url: &str) -> anyhow::Result<()> {
+    let meta = Meta {
+        url: url.to_string(),
+        size: fs::metadata(path)?.len(),
+        sha256: checksum::sha256_file(path)?,
+    };
+    fs::write(meta_path(path), serde_json::to_string_pretty(&meta)?)?;
+    Ok(())
+}
+
+/// A file or directory in the cache directory
+pub struct Entry {
+    pub path: PathBuf,
+    /// What the entry is, such as the URL of a download
+    pub description: String,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// List entries in the cache directory, including downloads, source trees, and the
+/// cached releases
+pub fn entries() -> anyhow::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    if !CACHE_DIR.exists() {
+        return Ok(entries);
+    }
+    for entry in CACHE_DIR.read_dir()? {
+        let path = entry?.path();
+        if path.is_dir() {
+            for entry in path.read_dir()? {
+                entries.push(new_entry(entry?.path())?);
+            }
+        } else {
+            entries.push(new_entry(path)?);
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+fn new_entry(path: PathBuf) -> anyhow::Result<Entry> {
+    let meta = fs::read_to_string(path.join("meta.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str::<Meta>(&content).ok());
+    let description = match meta {
+        Some(meta) => meta.url,
+        None => path
+            .strip_prefix(&*CACHE_DIR)
+            .unwrap_or(&path)
+            .display()
+            .to_string(),
+    };
+    Ok(Entry {
+        size: dir_size(&path)?,
+        modified: fs::metadata(&path)?.modified()?,
+        description,
+        path,
+    })
+}
+
+fn dir_size(path: &Path) -> anyhow::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in path.read_dir()? {
+        size += dir_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// Remove entries last modified before `older_than`, or all entries if it is `None`.
+/// Returns the total size of the removed entries.
+pub fn clean(older_than: Option<Duration>) -> anyhow::Result<u64> {
+    let now = SystemTime::now();
+    let mut removed = 0;
+    for entry in entries()? {
+        let age = now.duration_since(entry.modified).unwrap_or_default();
+        if older_than.is_some_and(|older_than| age < older_than) {
+            continue;
+        }
+        if entry.path.is_dir() {
+            fs::remove_dir_all(&entry.path)?;
+        } else {
+            fs::remove_file(&entry.path)?;
+        }
+        removed += entry.size;
+    }
+    Ok(removed)
+}
+
+/// Parse a duration such as `30d`, `12h`, or `2w`
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let unit_at = s
+        .find(|c: char| !c.is_ascii_digit())
+        .with_context(|| format!("Missing unit in {s:?}"))?;
+    let (number, unit) = s.split_at(unit_at);
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("Invalid number in {s:?}"))?;
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => anyhow::bail!("Unknown unit {unit:?}, expected one of s, m, h, d, or w"),
+    };
+    Ok(Duration::from_secs(number * secs))
+}
+
+#[test]
+fn parse_duration_t() {
+    assert_eq!(
+        parse_duration("30d").unwrap(),
+        Duration::from_secs(30 * 86400)
+    );
+    assert_eq!(
+        parse_duration("12h").unwrap(),
+        Duration::from_secs(12 * 3600)
+    );
+    assert_eq!(
+        parse_duration("2w").unwrap(),
+        Duration::from_secs(14 * 86400)
+    );
+    assert!(parse_duration("30").is_err());
+    assert!(parse_duration("d").is_err());
+    assert!(parse_duration("3y").is_err());
+}
@@ -0,0 +1,65 @@
+use std::{fs::File, io, path::Path};
+
+use sha2::{Digest as _, Sha256};
+
+/// Find the SHA-256 hash of `file_name` in the content of a checksum file.
+///
+/// Each line of the content is `<hash>  <file name>` as written by `sha256sum`.
+/// A line without a file name is used if it is the only one.
+pub fn find_sha256(content: &str, file_name: &str) -> Option<String> {
+    let entries = content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let hash = fields.next()?;
+            let name = fields.next().map(|name| name.trim_start_matches('*'));
+            Some((hash, name))
+        })
+        .collect::<Vec<_>>();
+
+    let hash = match entries[..] {
+        [(hash, None)] => hash,
+        _ => entries.iter().find_map(|(hash, name)| {
+            let name = Path::new((*name)?).file_name()?;
+            (name == file_name).then_some(*hash)
+        })?,
+    };
+
+    Some(hash.to_ascii_lowercase())
+}
+
+/// Compute the SHA-256 hash of a file as a lowercase hex string
+pub fn sha256_file(path: impl AsRef<Path>) -> anyhow::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[test]
+fn find_sha256_t() {
+    let s = [
+        (
+            "ABC123  nvim-linux64.tar.gz\n",
+            "nvim-linux64.tar.gz",
+            Some("abc123"),
+        ),
+        ("abc123\n", "nvim-win64.zip", Some("abc123")),
+        (
+            "aaa  nvim-win64.zip\nbbb *nvim-linux64.tar.gz\n",
+            "nvim-linux64.tar.gz",
+            Some("bbb"),
+        ),
+        (
+            "aaa  ./build/nvim-win64.zip\n",
+            "nvim-win64.zip",
+            Some("aaa"),
+        ),
+        ("aaa  nvim-win64.zip\n", "nvim-macos.tar.gz", None),
+        ("", "nvim-macos.tar.gz", None),
+    ];
+
+    for (content, file_name, hash) in s {
+        assert_eq!(find_sha256(content, file_name).as_deref(), hash);
+    }
+}
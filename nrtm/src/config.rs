@@ -0,0 +1,150 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use anyhow::Context as _;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use crate::BASE_DIR;
+
+pub static CONFIG_FILE: Lazy<PathBuf> = Lazy::new(|| BASE_DIR.join("config.toml"));
+
+/// User configuration read from `config.toml` in the base directory
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub nightly: NightlyConfig,
+    pub source: SourceConfig,
+    pub download: DownloadConfig,
+    /// Executables launched through links to the shim, keyed by the link name
+    pub tools: BTreeMap<String, ToolConfig>,
+    /// Launch settings for an installed version such as `v0.9.5`, `nightly`, or
+    /// `system`
+    pub version: BTreeMap<String, Profile>,
+    /// Launch settings for an NVIM_APPNAME
+    pub app: BTreeMap<String, Profile>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NightlyConfig {
+    /// Number of nightly builds kept for rollback, including the current one
+    pub keep: usize,
+}
+
+impl Default for NightlyConfig {
+    fn default() -> Self {
+        NightlyConfig { keep: 3 }
+    }
+}
+
+/// Where releases are fetched from.
+///
+/// Run `nrtm update --full` after changing it, since cached releases are merged with
+/// fetched ones.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourceConfig {
+    /// Base URL of the GitHub API, such as `https://github.example.com/api/v3` for
+    /// GitHub Enterprise
+    pub api_url: String,
+    /// Repository to fetch releases from
+    pub repo: String,
+    /// URL of a JSON index in the format of GitHub's release list, used instead of
+    /// the GitHub API. Relative download URLs are resolved against it.
+    pub mirror: Option<String>,
+}
+
+impl Default for SourceConfig {
+    fn default() -> Self {
+        SourceConfig {
+            api_url: "https://api.github.com".into(),
+            repo: "neovim/neovim".into(),
+            mirror: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DownloadConfig {
+    /// Seconds to wait for data before giving up on a connection
+    pub timeout: u64,
+    /// Number of times a failed download is retried
+    pub retries: u32,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        DownloadConfig {
+            timeout: 30,
+            retries: 5,
+        }
+    }
+}
+
+/// How the shim launches a tool other than `nvim`, such as a GUI.
+///
+/// `{nvim}` in the values is replaced with the path to `nvim` of the selected version.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolConfig {
+    /// Executable to run, looked up in $PATH if it is a bare name. Defaults to the one
+    /// with the same name next to `nvim`
+    pub path: Option<String>,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+}
+
+/// Environment variables and arguments added when the shim launches Neovim
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub env: BTreeMap<String, String>,
+    /// Passed to `nvim` before the arguments given to the shim
+    pub args: Vec<String>,
+}
+
+impl Config {
+    pub fn load() -> anyhow::Result<Config> {
+        if !CONFIG_FILE.exists() {
+            return Ok(Config::default());
+        }
+        let content = fs::read_to_string(&*CONFIG_FILE)?;
+        let config = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", CONFIG_FILE.display()))?;
+        Ok(config)
+    }
+
+    /// Profiles for `version` and `appname` in the order they are applied, so that
+    /// the one for `appname` takes precedence
+    pub fn profiles<'a>(
+        &'a self,
+        version: Option<&str>,
+        appname: Option<&str>,
+    ) -> impl Iterator<Item = &'a Profile> {
+        let version = version.and_then(|v| self.version.get(v));
+        let app = appname.and_then(|a| self.app.get(a));
+        version.into_iter().chain(app)
+    }
+}
+
+#[test]
+fn profiles_t() {
+    let config: Config = toml::from_str(
+        r#"
+        [version."v0.9.5"]
+        env = { VIMRUNTIME = "/opt/runtime" }
+        args = ["--clean"]
+
+        [app.test]
+        env = { XDG_CONFIG_HOME = "/tmp/test" }
+        "#,
+    )
+    .unwrap();
+
+    let profiles: Vec<_> = config.profiles(Some("v0.9.5"), Some("test")).collect();
+    assert_eq!(profiles.len(), 2);
+    assert_eq!(profiles[0].args, ["--clean"]);
+    assert_eq!(profiles[1].env["XDG_CONFIG_HOME"], "/tmp/test");
+    assert_eq!(config.profiles(Some("v0.10.0"), None).count(), 0);
+}
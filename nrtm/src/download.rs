@@ -0,0 +1,216 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use futures_util::StreamExt as _;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use reqwest::{header, Client, Response, StatusCode};
+use tokio::time::{sleep, timeout};
+
+use crate::config::DownloadConfig;
+
+/// Download `url` into `path`, resuming and retrying when the connection drops.
+///
+/// The partial file is kept as `<path>.part`, so an interrupted download is resumed
+/// on the next run as well. Its ETag or Last-Modified is kept beside it and sent as
+/// `If-Range`, so that the download starts over if the file has changed since, as
+/// nightly builds do.
+pub async fn download_file(
+    client: &Client,
+    url: &str,
+    path: impl AsRef<Path>,
+    config: &DownloadConfig,
+    progress: &MultiProgress,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let part_path = part_path(path);
+    let mut attempt = 0;
+    loop {
+        match download_part(client, url, &part_path, config, progress).await {
+            Ok(()) => break,
+            Err(e) if attempt < config.retries && is_retryable(&e) => {
+                attempt += 1;
+                let delay = Duration::from_secs(1 << attempt.min(5));
+                progress.suspend(|| {
+                    eprintln!(
+                        "Download failed: {e:#}. Retrying in {}s ({attempt}/{})...",
+                        delay.as_secs(),
+                        config.retries,
+                    )
+                });
+                sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    fs::rename(&part_path, path)?;
+    let validator_path = validator_path(&part_path);
+    if validator_path.exists() {
+        fs::remove_file(validator_path)?;
+    }
+    Ok(())
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".part")
+}
+
+/// Path to the validator of the content in `part_path`
+fn validator_path(part_path: &Path) -> PathBuf {
+    with_suffix(part_path, ".validator")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Strong validator of a response to use as `If-Range`
+fn validator(res: &Response) -> Option<&str> {
+    let headers = res.headers();
+    // Weak ETags cannot be used for ranges.
+    let etag = headers
+        .get(header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"));
+    etag.or_else(|| {
+        headers
+            .get(header::LAST_MODIFIED)
+            .and_then(|v| v.to_str().ok())
+    })
+}
+
+/// Download the rest of `url` into `part_path`, continuing from its current length
+async fn download_part(
+    client: &Client,
+    url: &str,
+    part_path: &Path,
+    config: &DownloadConfig,
+    progress: &MultiProgress,
+) -> anyhow::Result<()> {
+    let stall_timeout = Duration::from_secs(config.timeout);
+    let validator_path = validator_path(part_path);
+    let res = loop {
+        let offset = fs::metadata(part_path).map_or(0, |m| m.len());
+        let mut req = client.get(url);
+        // Without a validator, the partial file may be of another version of the
+        // file, so it is only resumed with one.
+        let stored = fs::read_to_string(&validator_path).ok();
+        let resume = offset > 0 && stored.is_some();
+        if let Some(validator) = stored.filter(|_| resume) {
+            req = req
+                .header(header::RANGE, format!("bytes={offset}-"))
+                .header(header::IF_RANGE, validator);
+        }
+        let res = timeout(stall_timeout, req.send()).await??;
+        // The partial file is stale or already complete, which cannot be told apart
+        // without the total size. Start over.
+        if res.status() == StatusCode::RANGE_NOT_SATISFIABLE && resume {
+            fs::remove_file(part_path)?;
+            continue;
+        }
+        break res.error_for_status()?;
+    };
+
+    let (mut file, offset) = if res.status() == StatusCode::PARTIAL_CONTENT {
+        let file = OpenOptions::new().append(true).open(part_path)?;
+        let offset = file.metadata()?.len();
+        (file, offset)
+    } else {
+        // The server ignored the range, or the file has changed.
+        match validator(&res) {
+            Some(validator) => fs::write(&validator_path, validator)?,
+            None if validator_path.exists() => fs::remove_file(&validator_path)?,
+            None => {}
+        }
+        (File::create(part_path)?, 0)
+    };
+
+    // Source tarballs are generated on the fly and have no known length.
+    let pb = if let Some(len) = res.content_length() {
+        ProgressBar::new(offset + len).with_style(ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] [{wide_bar:.cyan/blue.dim}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")?
+            .progress_chars("━╸╌"))
+    } else {
+        ProgressBar::new_spinner().with_style(
+            ProgressStyle::default_spinner()
+                .template("[{elapsed_precise}] {spinner} {bytes} ({bytes_per_sec})")?,
+        )
+    };
+    let pb = progress.add(pb);
+    pb.set_position(offset);
+
+    let mut stream = res.bytes_stream();
+    while let Some(item) = timeout(stall_timeout, stream.next()).await? {
+        let chunk = item?;
+        file.write_all(&chunk)?;
+        pb.inc(chunk.len() as u64);
+    }
+    pb.finish();
+
+    Ok(())
+}
+
+/// Whether the download may succeed if tried again
+fn is_retryable(e: &anyhow::Error) -> bool {
+    if e.is::<tokio::time::error::Elapsed>() {
+        return true;
+    }
+    if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+        return match e.status() {
+            Some(status) => {
+                status.is_server_error()
+                    || status == StatusCode::REQUEST_TIMEOUT
+                    || status == StatusCode::TOO_MANY_REQUESTS
+            }
+            None => !e.is_builder(),
+        };
+    }
+    false
+}
+
+#[tokio::test]
+async fn download_file_t() {
+    let mut server = mockito::Server::new_async().await;
+    let resumed = server
+        .mock("GET", "/nvim.tar.gz")
+        .match_header("range", "bytes=5-")
+        .match_header("if-range", "\"v1\"")
+        .with_status(206)
+        .with_body("world")
+        .create_async()
+        .await;
+    // Such as a nightly build rebuilt since the download was interrupted
+    let changed = server
+        .mock("GET", "/nightly.tar.gz")
+        .match_header("range", "bytes=5-")
+        .match_header("if-range", "\"v1\"")
+        .with_status(200)
+        .with_header("etag", "\"v2\"")
+        .with_body("today")
+        .create_async()
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = DownloadConfig::default();
+    for (name, expected) in [("nvim.tar.gz", "helloworld"), ("nightly.tar.gz", "today")]
+    {
+        let path = dir.path().join(name);
+        fs::write(part_path(&path), "hello").unwrap();
+        fs::write(validator_path(&part_path(&path)), "\"v1\"").unwrap();
+        let url = format!("{}/{name}", server.url());
+        download_file(&Client::new(), &url, &path, &config, &MultiProgress::new())
+            .await
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+        assert!(!part_path(&path).exists());
+        assert!(!validator_path(&part_path(&path)).exists());
+    }
+    resumed.assert_async().await;
+    changed.assert_async().await;
+}
@@ -1,43 +1,269 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::Read as _,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::Context as _;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use reqwest::{
     header::{self, HeaderMap, HeaderValue},
-    Client,
+    Client, Response, StatusCode, Url,
 };
 use semver::Version;
 use serde::Deserialize;
 
-use crate::CACHE_DIR;
+use crate::{
+    config::{Config, SourceConfig},
+    target::Target,
+    CACHE_DIR,
+};
 
 static API_VERSION: &str = "2022-11-28";
 static USER_AGENT: &str =
     concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 static CACHE_FILE: Lazy<PathBuf> = Lazy::new(|| CACHE_DIR.join("releases.json"));
 
-pub async fn cache_response() -> anyhow::Result<String> {
-    let request_url = "https://api.github.com/repos/neovim/neovim/releases";
+/// Fetch releases from the configured source and merge them into the cache.
+///
+/// GitHub is paged until a page only contains releases already cached, so the first
+/// call fetches the full history and later ones fetch only new releases. If `full`
+/// is true, the cache is ignored and the full history is fetched again.
+pub async fn cache_response(full: bool) -> anyhow::Result<String> {
+    let source = Config::load()?.source;
+    let cached = if full {
+        Vec::new()
+    } else {
+        read_cached_values()
+    };
+
+    let fetched = if let Some(mirror) = &source.mirror {
+        fetch_mirror(mirror).await?
+    } else {
+        let known_tags = cached.iter().filter_map(tag_name).collect::<HashSet<_>>();
+        fetch_github(&releases_url(&source), &known_tags).await?
+    };
+
+    let fetched_tags = fetched
+        .iter()
+        .filter_map(tag_name)
+        .map(String::from)
+        .collect::<HashSet<_>>();
+    let merged = fetched
+        .iter()
+        .chain(
+            cached
+                .iter()
+                .filter(|r| tag_name(r).is_some_and(|tag| !fetched_tags.contains(tag))),
+        )
+        .collect::<Vec<_>>();
+
+    let json = serde_json::to_string(&merged)?;
+    fs::write(&*CACHE_FILE, &json)?;
+
+    Ok(json)
+}
+
+fn releases_url(source: &SourceConfig) -> String {
+    format!(
+        "{}/repos/{}/releases?per_page=100",
+        source.api_url.trim_end_matches('/'),
+        source.repo,
+    )
+}
+
+/// Fetch pages of releases from the GitHub API until a page only contains
+/// `known_tags`
+async fn fetch_github(
+    url: &str,
+    known_tags: &HashSet<&str>,
+) -> anyhow::Result<Vec<serde_json::Value>> {
+    let client = new_client()?;
+    let mut fetched = Vec::new();
+    let mut request_url = Some(url.to_string());
+
+    while let Some(url) = request_url {
+        let res = client
+            .get(&url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .send()
+            .await?;
+        let res = ApiError::check(res).await?;
+        request_url = res
+            .headers()
+            .get(header::LINK)
+            .and_then(|link| link.to_str().ok())
+            .and_then(parse_next_link);
+
+        let page: Vec<serde_json::Value> = serde_json::from_str(&res.text().await?)?;
+        // `nightly` and `stable` are rebuilt under the same tag, so they never tell
+        // whether the rest is cached.
+        let all_known = page
+            .iter()
+            .filter_map(tag_name)
+            .all(|tag| known_tags.contains(tag) || !tag.starts_with('v'));
+        fetched.extend(page);
+        if all_known {
+            break;
+        }
+    }
+
+    Ok(fetched)
+}
 
-    let json = new_client()?
-        .get(request_url)
-        .header(header::CONTENT_TYPE, "application/json")
+/// Fetch the release index of a mirror, resolving relative download URLs
+async fn fetch_mirror(url: &str) -> anyhow::Result<Vec<serde_json::Value>> {
+    // The GitHub token is not sent to mirrors.
+    let client = Client::builder().user_agent(USER_AGENT).build()?;
+    let json = client
+        .get(url)
         .send()
         .await?
+        .error_for_status()?
         .text()
         .await?;
+    let mut releases: Vec<serde_json::Value> = serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse the release index from {url}"))?;
 
-    fs::write(&*CACHE_FILE, &json)?;
+    let base = Url::parse(url)?;
+    for release in &mut releases {
+        let Some(assets) = release.get_mut("assets").and_then(|a| a.as_array_mut())
+        else {
+            continue;
+        };
+        for asset in assets {
+            let Some(download_url) = asset
+                .get_mut("browser_download_url")
+                .filter(|u| u.is_string())
+            else {
+                continue;
+            };
+            let resolved = base.join(download_url.as_str().unwrap())?;
+            *download_url = resolved.as_str().into();
+        }
+    }
 
-    Ok(json)
+    Ok(releases)
+}
+
+#[tokio::test]
+async fn fetch_github_t() {
+    let mut server = mockito::Server::new_async().await;
+    let url = format!("{}/repos/neovim/neovim/releases?per_page=100", server.url());
+    let page1 = server
+        .mock("GET", "/repos/neovim/neovim/releases")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "per_page".into(),
+            "100".into(),
+        ))
+        .with_header("link", &format!(r#"<{}/page2>; rel="next""#, server.url()))
+        .with_body(r#"[{"tag_name": "nightly"}, {"tag_name": "v0.10.0"}]"#)
+        .expect(2)
+        .create_async()
+        .await;
+    let page2 = server
+        .mock("GET", "/page2")
+        .with_body(r#"[{"tag_name": "v0.9.5"}]"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let fetched = fetch_github(&url, &HashSet::new()).await.unwrap();
+    assert_eq!(fetched.len(), 3);
+
+    // The second page is not requested if the first one is already cached.
+    let fetched = fetch_github(&url, &HashSet::from(["v0.10.0"]))
+        .await
+        .unwrap();
+    assert_eq!(fetched.len(), 2);
+
+    page1.assert_async().await;
+    page2.assert_async().await;
+}
+
+#[tokio::test]
+async fn fetch_mirror_t() {
+    let mut server = mockito::Server::new_async().await;
+    let index = r#"[{
+        "tag_name": "v0.9.5",
+        "assets": [
+            {"name": "a.zip", "content_type": "application/zip", "browser_download_url": "v0.9.5/a.zip"},
+            {"name": "b.zip", "content_type": "application/zip", "browser_download_url": "https://example.com/b.zip"}
+        ]
+    }]"#;
+    let mock = server
+        .mock("GET", "/nvim/index.json")
+        .with_body(index)
+        .create_async()
+        .await;
+
+    let releases = fetch_mirror(&format!("{}/nvim/index.json", server.url()))
+        .await
+        .unwrap();
+    mock.assert_async().await;
+    let assets = &releases[0]["assets"];
+    assert_eq!(
+        assets[0]["browser_download_url"],
+        format!("{}/nvim/v0.9.5/a.zip", server.url())
+    );
+    assert_eq!(
+        assets[1]["browser_download_url"],
+        "https://example.com/b.zip"
+    );
+
+    let releases: Vec<Release> = serde_json::from_value(releases.into()).unwrap();
+    assert_eq!(releases[0].get_nvim_version().ok(), None);
+}
+
+fn read_cached_values() -> Vec<serde_json::Value> {
+    fs::read_to_string(&*CACHE_FILE)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn tag_name(release: &serde_json::Value) -> Option<&str> {
+    release.get("tag_name")?.as_str()
+}
+
+/// Get the URL with `rel="next"` from the value of a `Link` header
+fn parse_next_link(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let (url, params) = part.split_once(';')?;
+        params
+            .split(';')
+            .any(|param| param.trim() == r#"rel="next""#)
+            .then(|| {
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+    })
+}
+
+#[test]
+fn parse_next_link_t() {
+    let link = concat!(
+        r#"<https://api.github.com/repositories/1/releases?per_page=100&page=2>; rel="next", "#,
+        r#"<https://api.github.com/repositories/1/releases?per_page=100&page=3>; rel="last""#,
+    );
+    assert_eq!(
+        parse_next_link(link).as_deref(),
+        Some("https://api.github.com/repositories/1/releases?per_page=100&page=2"),
+    );
+    let link = r#"<https://api.github.com/repositories/1/releases?page=1>; rel="prev""#;
+    assert_eq!(parse_next_link(link), None);
 }
 
 pub async fn get_releases() -> anyhow::Result<Vec<Release>> {
     let json = if CACHE_FILE.exists() {
         fs::read_to_string(&*CACHE_FILE)?
     } else {
-        cache_response().await?
+        cache_response(false).await?
     };
 
     let releases = serde_json::from_str(&json)?;
@@ -48,10 +274,17 @@ pub async fn get_releases() -> anyhow::Result<Vec<Release>> {
 #[derive(Deserialize)]
 #[cfg_attr(test, derive(Default))]
 pub struct Release {
+    #[serde(default)]
     pub name: String,
     pub tag_name: String,
+    #[serde(default)]
     pub body: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub published_at: Option<String>,
     pub assets: Vec<Asset>,
+    #[serde(default)]
     pub html_url: String,
 }
 
@@ -63,45 +296,46 @@ impl Release {
         }
     }
 
-    pub fn filter_assets(&self) -> Option<&Asset> {
-        let invalid_os = [
-            #[cfg(not(target_os = "linux"))]
-            "linux",
-            #[cfg(not(target_os = "macos"))]
-            "macos",
-            #[cfg(not(target_os = "windows"))]
-            "win",
-        ];
-        let invalid_pointer_width = [
-            #[cfg(not(target_pointer_width = "32"))]
-            "32",
-            #[cfg(not(target_pointer_width = "64"))]
-            "64",
-        ];
-
-        'asset: for asset in &self.assets {
-            if asset.get_type().is_none() {
-                continue;
-            }
-            for os_name in &invalid_os {
-                if asset.name.contains(os_name) {
-                    continue 'asset;
-                }
-            }
-            for pointer_width in &invalid_pointer_width {
-                if asset.name.contains(pointer_width) {
-                    continue 'asset;
-                }
-            }
+    /// Name of the directory the release is installed into.
+    ///
+    /// Nightly releases are rebuilt under the same tag, so they are named after the
+    /// actual version. Tags from a mirror or a fork that cannot be a directory name,
+    /// such as `release/0.9`, are rejected.
+    pub fn install_name(&self) -> anyhow::Result<String> {
+        let name = if self.tag_name == "nightly" {
+            format!("v{}", self.get_nvim_version()?)
+        } else {
+            self.tag_name.clone()
+        };
+        crate::validate_install_name(&name)?;
+        Ok(name)
+    }
 
-            return Some(asset);
-        }
+    /// Find the asset to install on `target`
+    pub fn filter_assets(&self, target: &Target) -> Option<&Asset> {
+        target.asset_names().iter().find_map(|name| {
+            self.assets
+                .iter()
+                .filter(|asset| asset.stem() == *name)
+                .filter_map(|asset| Some((asset.get_type()?, asset)))
+                // An archive is preferred to the AppImage published alongside it.
+                .min_by_key(|(asset_type, _)| *asset_type)
+                .map(|(_, asset)| asset)
+        })
+    }
 
-        None
+    /// Find the asset that contains the SHA-256 hash of `asset`
+    pub fn checksum_asset(&self, asset: &Asset) -> Option<&Asset> {
+        let sha256sum = format!("{}.sha256sum", asset.name);
+        self.assets
+            .iter()
+            .find(|a| a.name == sha256sum)
+            .or_else(|| self.assets.iter().find(|a| a.name == "shasum.txt"))
     }
 }
 
-fn get_nvim_version(text: &str) -> anyhow::Result<Version> {
+/// Get Neovim version from text such as the output of `nvim --version`
+pub fn get_nvim_version(text: &str) -> anyhow::Result<Version> {
     static RE: Lazy<Regex> = Lazy::new(|| {
         Regex::new(
             r"(^|\W)(?i)nvim(?-i) v?(?<version>\d+\.\d+\.\d+(-\S+)*(\+\S+)*)(\W|$)",
@@ -114,12 +348,27 @@ fn get_nvim_version(text: &str) -> anyhow::Result<Version> {
         .with_context(|| format!("Failed to get Neovim version from text: {text}"))?;
     let raw_version = caps.name("version").map_or("", |m| m.as_str());
 
-    let version = if let Some((version_prerelease, build_metadata)) =
-        raw_version.rsplit_once('-')
-    {
-        Version::parse(&format!("{version_prerelease}+{build_metadata}"))?
+    // Nightly builds are versioned like `0.10.0-dev-1234+gabcdef`.
+    let (raw_version, commit) = match raw_version.split_once('+') {
+        Some((raw_version, commit)) => (raw_version, Some(commit)),
+        None => (raw_version, None),
+    };
+    let (version_prerelease, build_number) = match raw_version.rsplit_once('-') {
+        Some((version_prerelease, build_number)) => {
+            (version_prerelease, Some(build_number))
+        }
+        None => (raw_version, None),
+    };
+    let build_metadata = [build_number, commit]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(".");
+
+    let version = if build_metadata.is_empty() {
+        Version::parse(version_prerelease)?
     } else {
-        Version::parse(raw_version)?
+        Version::parse(&format!("{version_prerelease}+{build_metadata}"))?
     };
 
     Ok(version)
@@ -131,6 +380,11 @@ fn get_nvim_version_t() {
     assert_eq!(version.unwrap(), Version::parse("1.0.0").unwrap());
     let version = get_nvim_version("foo\n```\nNvim v1.0.0-dev-1234 (bar)\n");
     assert_eq!(version.unwrap(), Version::parse("1.0.0-dev+1234").unwrap());
+    let version = get_nvim_version("NVIM v1.0.0-dev-1234+gabcdef\nBuild type: Release");
+    assert_eq!(
+        version.unwrap(),
+        Version::parse("1.0.0-dev+1234.gabcdef").unwrap()
+    );
 }
 
 #[derive(Deserialize)]
@@ -141,20 +395,77 @@ pub struct Asset {
 }
 
 impl Asset {
+    /// Type of the asset, judged by its file name first since GitHub's content types
+    /// are unreliable
     pub fn get_type(&self) -> Option<AssetType> {
+        use AssetType::*;
+        if let Some((_, asset_type)) = self.split_extension() {
+            return Some(asset_type);
+        }
         match self.content_type.as_str() {
-            "application/x-zip-compressed" | "application/zip" => Some(AssetType::Zip),
+            "application/x-zip-compressed" | "application/zip" => Some(Zip),
             "application/x-gtar" | "application/x-gzip" | "application/gzip" => {
-                Some(AssetType::TarGz)
+                Some(TarGz)
             }
+            "application/x-xz" => Some(TarXz),
+            "application/zstd" => Some(TarZst),
+            "application/vnd.appimage" => Some(AppImage),
             _ => None,
         }
     }
+
+    /// Name of the asset without the extension of its type, such as `nvim-win64`
+    pub fn stem(&self) -> &str {
+        self.split_extension().map_or(&self.name, |(stem, _)| stem)
+    }
+
+    fn split_extension(&self) -> Option<(&str, AssetType)> {
+        use AssetType::*;
+        let extensions = [
+            (".zip", Zip),
+            (".tar.gz", TarGz),
+            (".tgz", TarGz),
+            (".tar.xz", TarXz),
+            (".txz", TarXz),
+            (".tar.zst", TarZst),
+            (".tzst", TarZst),
+            (".appimage", AppImage),
+        ];
+        let name = self.name.to_lowercase();
+        let (ext, asset_type) =
+            extensions.iter().find(|(ext, _)| name.ends_with(ext))?;
+        Some((&self.name[..self.name.len() - ext.len()], *asset_type))
+    }
 }
 
+/// Type of an installable asset, in the order of preference
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum AssetType {
     Zip,
     TarGz,
+    TarXz,
+    TarZst,
+    /// Self-mounting executable, which is extracted by running it
+    AppImage,
+}
+
+impl AssetType {
+    /// Detect the type of an archive from its magic number
+    pub fn detect(path: impl AsRef<Path>) -> anyhow::Result<AssetType> {
+        use AssetType::*;
+        let path = path.as_ref();
+        let mut magic = [0; 11];
+        let mut file = fs::File::open(path)?;
+        let len = file.read(&mut magic)?;
+        match &magic[..len] {
+            [b'P', b'K', 3, 4, ..] => Ok(Zip),
+            [0x1f, 0x8b, ..] => Ok(TarGz),
+            [0xfd, b'7', b'z', b'X', b'Z', 0, ..] => Ok(TarXz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Ok(TarZst),
+            [0x7f, b'E', b'L', b'F', _, _, _, _, b'A', b'I', 2] => Ok(AppImage),
+            _ => anyhow::bail!("Unknown archive format: {}", path.display()),
+        }
+    }
 }
 
 impl std::fmt::Display for AssetType {
@@ -163,17 +474,172 @@ impl std::fmt::Display for AssetType {
         match *self {
             Zip => write!(f, "zip")?,
             TarGz => write!(f, "tar.gz")?,
+            TarXz => write!(f, "tar.xz")?,
+            TarZst => write!(f, "tar.zst")?,
+            AppImage => write!(f, "appimage")?,
         }
         Ok(())
     }
 }
 
-fn new_client() -> anyhow::Result<Client> {
+#[test]
+fn get_type_t() {
+    let asset = |name: &str, content_type: &str| Asset {
+        name: name.into(),
+        content_type: content_type.into(),
+        browser_download_url: String::new(),
+    };
+    let s = [
+        ("nvim-win64.zip", "application/zip", Some(AssetType::Zip)),
+        (
+            "nvim-linux64.tar.gz",
+            "application/octet-stream",
+            Some(AssetType::TarGz),
+        ),
+        (
+            "nvim-linux64.tar.xz",
+            "application/octet-stream",
+            Some(AssetType::TarXz),
+        ),
+        ("nvim.tar.zst", "application/zstd", Some(AssetType::TarZst)),
+        (
+            "nvim.appimage",
+            "application/octet-stream",
+            Some(AssetType::AppImage),
+        ),
+        ("nvim", "application/gzip", Some(AssetType::TarGz)),
+        ("nvim.appimage.zsync", "application/octet-stream", None),
+        (
+            "nvim-linux64.tar.gz.sha256sum",
+            "application/octet-stream",
+            None,
+        ),
+    ];
+    for (name, content_type, asset_type) in s {
+        assert_eq!(asset(name, content_type).get_type(), asset_type, "{name}");
+    }
+}
+
+/// Error returned by the GitHub API instead of the expected response
+#[derive(Debug)]
+pub enum ApiError {
+    /// The rate limit is exceeded until `reset`
+    RateLimited {
+        reset: Option<SystemTime>,
+    },
+    Status {
+        status: StatusCode,
+        message: String,
+    },
+}
+
+impl ApiError {
+    /// Check the response and return an error if it is not successful
+    async fn check(res: Response) -> anyhow::Result<Response> {
+        if res.status().is_success() {
+            return Ok(res);
+        }
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.text().await.unwrap_or_default();
+        Err(Self::from_parts(status, &headers, &body).into())
+    }
+
+    fn from_parts(status: StatusCode, headers: &HeaderMap, body: &str) -> ApiError {
+        let header = |name: &str| -> Option<u64> {
+            headers.get(name)?.to_str().ok()?.parse().ok()
+        };
+
+        let rate_limited = status == StatusCode::TOO_MANY_REQUESTS
+            || (status == StatusCode::FORBIDDEN
+                && (header("x-ratelimit-remaining") == Some(0)
+                    || headers.contains_key(header::RETRY_AFTER)));
+        if rate_limited {
+            let reset = match header(header::RETRY_AFTER.as_str()) {
+                Some(secs) => Some(SystemTime::now() + Duration::from_secs(secs)),
+                None => header("x-ratelimit-reset")
+                    .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            };
+            return ApiError::RateLimited { reset };
+        }
+
+        let message = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|v| Some(v.get("message")?.as_str()?.to_string()))
+            .unwrap_or_else(|| body.to_string());
+        ApiError::Status { status, message }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ApiError::RateLimited { reset } => {
+                write!(f, "GitHub API rate limit exceeded.")?;
+                if let Some(reset) = reset {
+                    let secs =
+                        reset.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+                    let minutes = reset
+                        .duration_since(SystemTime::now())
+                        .map_or(0, |d| d.as_secs().div_ceil(60));
+                    write!(
+                        f,
+                        " It resets in {minutes} minutes (at {secs} in Unix time)."
+                    )?;
+                }
+                write!(f, " Set GITHUB_TOKEN or GH_TOKEN to raise the limit.")?;
+            }
+            ApiError::Status { status, message } => {
+                write!(f, "GitHub API returned {status}: {message}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[test]
+fn api_error_t() {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-ratelimit-remaining", HeaderValue::from_static("0"));
+    headers.insert("x-ratelimit-reset", HeaderValue::from_static("1700000000"));
+    let error = ApiError::from_parts(StatusCode::FORBIDDEN, &headers, "{}");
+    assert!(matches!(
+        error,
+        ApiError::RateLimited { reset: Some(reset) }
+            if reset == UNIX_EPOCH + Duration::from_secs(1700000000)
+    ));
+
+    let body = r#"{"message": "Bad credentials"}"#;
+    let error = ApiError::from_parts(StatusCode::UNAUTHORIZED, &HeaderMap::new(), body);
+    assert_eq!(
+        error.to_string(),
+        "GitHub API returned 401 Unauthorized: Bad credentials"
+    );
+}
+
+/// Token used to authenticate requests, read from `$GITHUB_TOKEN` or `$GH_TOKEN`
+fn get_token() -> Option<String> {
+    ["GITHUB_TOKEN", "GH_TOKEN"]
+        .into_iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|token| !token.is_empty())
+}
+
+/// Client for the GitHub API, which requires a User-Agent, authenticated with the
+/// token if any
+pub fn new_client() -> anyhow::Result<Client> {
     let mut headers = HeaderMap::new();
     headers.insert(
         "X-GitHub-Api-Version",
         HeaderValue::from_static(API_VERSION),
     );
+    if let Some(token) = get_token() {
+        let mut value = HeaderValue::from_str(&format!("Bearer {token}"))?;
+        value.set_sensitive(true);
+        headers.insert(header::AUTHORIZATION, value);
+    }
 
     let client = Client::builder()
         .user_agent(USER_AGENT)
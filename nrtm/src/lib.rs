@@ -1,9 +1,29 @@
+pub mod app;
+pub mod archive;
+pub mod bisect;
+pub mod cache;
+pub mod checksum;
+pub mod config;
+pub mod download;
 pub mod github;
+pub mod nightly;
+pub mod project;
 pub mod shim;
+pub mod source;
+pub mod target;
+pub mod version;
 
-use std::{env, fs, path::PathBuf};
+use std::{
+    env,
+    env::consts::EXE_SUFFIX,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::Command,
+};
 
+use anyhow::Context as _;
 use once_cell::sync::Lazy;
+use semver::Version;
 
 pub static BIN_DIR: Lazy<PathBuf> = Lazy::new(|| {
     env::current_exe()
@@ -23,3 +43,138 @@ fn init_dir(name: &str) -> PathBuf {
     fs::create_dir_all(&path).unwrap();
     path
 }
+
+/// Names of all versions installed in `NVIM_DIR`
+pub fn installed_versions() -> anyhow::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in NVIM_DIR.read_dir()? {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        // Dot-directories are unfinished installs.
+        match entry.file_name().to_str() {
+            Some(name) if !name.starts_with('.') => names.push(name.to_string()),
+            _ => {}
+        }
+    }
+    Ok(names)
+}
+
+/// Path to the `nvim` executable of an installed version
+pub fn nvim_exe(name: &str) -> PathBuf {
+    NVIM_DIR.join(format!("{name}/bin/nvim{EXE_SUFFIX}"))
+}
+
+/// Check that `name` can be installed as a directory directly under `NVIM_DIR`
+pub fn validate_install_name(name: &str) -> anyhow::Result<()> {
+    // Dot-directories are reserved for unfinished installs.
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        anyhow::bail!(
+            "Invalid name {name:?}, which must not contain `/` or `\\` or start with `.`."
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn validate_install_name_t() {
+    for name in ["v0.9.5", "nightly", "master", "feature-x"] {
+        assert!(validate_install_name(name).is_ok(), "{name}");
+    }
+    for name in ["", ".", "..", "../x", "a/b", "a\\b", ".staging-x"] {
+        assert!(validate_install_name(name).is_err(), "{name}");
+    }
+}
+
+/// Empty directory to install `name` into before it is moved into place
+pub fn staging_dir(name: &str) -> anyhow::Result<PathBuf> {
+    let path = NVIM_DIR.join(format!(".staging-{name}"));
+    if path.exists() {
+        fs::remove_dir_all(&path)?;
+    }
+    fs::create_dir_all(&path)?;
+    Ok(path)
+}
+
+/// Move the version installed into `staging` into place as `name`, replacing the
+/// installed one if any. If `check` is true, the version must run first.
+pub fn commit_install(staging: &Path, name: &str, check: bool) -> anyhow::Result<()> {
+    if check {
+        let exe_path = staging.join(format!("bin/nvim{EXE_SUFFIX}"));
+        if let Err(e) = nvim_version(&exe_path) {
+            fs::remove_dir_all(staging)?;
+            return Err(e.context("The installed Neovim does not work."));
+        }
+    }
+
+    let target = NVIM_DIR.join(name);
+    let old = NVIM_DIR.join(format!(".old-{name}"));
+    if target.exists() {
+        if old.exists() {
+            fs::remove_dir_all(&old)?;
+        }
+        fs::rename(&target, &old)?;
+    }
+    if let Err(e) = fs::rename(staging, &target) {
+        if old.exists() {
+            fs::rename(&old, &target)?;
+        }
+        return Err(e).with_context(|| format!("Failed to move {name} into place"));
+    }
+    if old.exists() {
+        fs::remove_dir_all(&old)?;
+    }
+    Ok(())
+}
+
+/// Lock `NVIM_DIR` for installing until the returned file is dropped, waiting for
+/// another nrtm holding it.
+///
+/// Staging directories are cleaned up and replaced versions are restored by any
+/// install, so they must not run at the same time.
+pub fn lock_installs() -> anyhow::Result<File> {
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(NVIM_DIR.join(".lock"))?;
+    if file.try_lock().is_err() {
+        eprintln!("Waiting for another nrtm to finish installing...");
+        file.lock()?;
+    }
+    Ok(file)
+}
+
+/// Remove directories left by interrupted installs, which must be called with the
+/// lock of [`lock_installs`] held
+pub fn clean_staging() -> anyhow::Result<()> {
+    for entry in NVIM_DIR.read_dir()? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with(".staging-") && !name.starts_with(".old-") {
+            continue;
+        }
+        // A replaced version is restored if the new one was not moved into place.
+        if let Some(name) = name.strip_prefix(".old-") {
+            if !NVIM_DIR.join(name).exists() {
+                fs::rename(entry.path(), NVIM_DIR.join(name))?;
+                continue;
+            }
+        }
+        fs::remove_dir_all(entry.path())?;
+    }
+    Ok(())
+}
+
+/// Run `nvim --version` to get the version of an executable
+pub fn nvim_version(exe_path: &Path) -> anyhow::Result<Version> {
+    let output = Command::new(exe_path)
+        .arg("--version")
+        .output()
+        .with_context(|| format!("Failed to run {}", exe_path.display()))?;
+    if !output.status.success() {
+        anyhow::bail!("`{} --version` failed.", exe_path.display());
+    }
+    github::get_nvim_version(&String::from_utf8_lossy(&output.stdout))
+}
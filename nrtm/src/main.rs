@@ -1,18 +1,27 @@
 use std::{
-    env::consts::EXE_SUFFIX,
-    fs::{self, File},
+    env::{self, consts::EXE_SUFFIX},
+    fs,
     io::{self, Write as _},
     path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
 };
 
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
-
+use anyhow::Context as _;
 use clap::Parser as _;
-use futures_util::StreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
+use futures_util::future;
+use indicatif::{HumanBytes, HumanDuration, MultiProgress};
 
-use nrtm::{github, shim, CACHE_DIR, NVIM_DIR};
+use nrtm::{
+    app, archive,
+    bisect::{Bisect, Outcome},
+    cache, checksum,
+    config::Config,
+    download::download_file,
+    github, nightly, nvim_version, shim, source,
+    target::Target,
+    version, CACHE_DIR, NVIM_DIR,
+};
 
 /// A runtime manager for Neovim
 #[derive(clap::Parser)]
@@ -28,19 +37,126 @@ enum Commands {
     #[command(name = "-")]
     Restore,
     /// Download a release
-    Get { version: String },
+    Get {
+        /// Tags, semver requirements (e.g. `0.9`, `^0.10`), `latest`, `stable`, or
+        /// `nightly`. Several versions are installed in parallel
+        #[arg(required_unless_present_any = ["from_source", "archive"])]
+        versions: Vec<String>,
+        /// Install even if the checksum of the downloaded archive does not match
+        #[arg(long)]
+        skip_checksum: bool,
+        /// Build a tag, commit, or branch from source instead
+        #[arg(long, value_name = "REF", conflicts_with = "versions")]
+        from_source: Option<String>,
+        /// Install a local zip, tar.gz, tar.xz, or tar.zst archive, or an AppImage
+        /// instead
+        #[arg(long, conflicts_with_all = ["versions", "from_source"])]
+        archive: Option<PathBuf>,
+        /// Name of the directory to install into, used with --from-source or
+        /// --archive. Defaults to the reference or the version of the archive
+        #[arg(long, conflicts_with = "versions")]
+        name: Option<String>,
+        /// CMAKE_BUILD_TYPE used with --from-source
+        #[arg(
+            long,
+            default_value = "RelWithDebInfo",
+            value_parser = source::BUILD_TYPES,
+        )]
+        build_type: String,
+        /// Platform to install the release for, such as `aarch64-linux`. Defaults to
+        /// the current one. Releases for another one are installed as
+        /// `<version>-<target>`
+        #[arg(long, conflicts_with_all = ["from_source", "archive"])]
+        target: Option<Target>,
+    },
     /// Remove the specified version
     Remove { version: String },
     /// Set version for use
-    Use { version: String },
+    Use {
+        /// Installed version, semver requirement, alias, or `system`
+        version: String,
+    },
+    /// Run an installed version without changing the version in use
+    Exec {
+        /// Installed version, semver requirement, alias, or `system`
+        version: String,
+        /// NVIM_APPNAME for this process
+        #[arg(long)]
+        appname: Option<String>,
+        /// Arguments passed to Neovim
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
     /// Print all installed versions
     List,
+    /// Print releases available for download
+    LsRemote {
+        /// Only print releases matching a semver requirement or alias
+        filter: Option<String>,
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// Print the path to an executable that used by shim
     Which,
     /// Manage NVIM_APPNAME
     App(AppArgs),
     /// Update cached response data
-    Update,
+    Update {
+        /// Fetch all releases again instead of only new ones
+        #[arg(long)]
+        full: bool,
+        /// Also install the newest nightly build and point `nightly` at it
+        #[arg(long)]
+        nightly: bool,
+    },
+    /// Point `nightly` at the build installed before the current one
+    Rollback,
+    /// Link a name such as `nvim-qt` to the shim, or print linked names
+    Link {
+        /// Executable shipped with Neovim or configured in `[tools.<name>]`
+        name: Option<String>,
+    },
+    /// Remove a link created by `link`
+    Unlink { name: String },
+    /// Manage downloaded files and cached releases
+    Cache(CacheArgs),
+    /// Find the first release that makes a command fail by binary search
+    Bisect {
+        /// A version known to be good
+        #[arg(long)]
+        good: String,
+        /// A version known to be bad
+        #[arg(long)]
+        bad: String,
+        /// Install releases even if their checksums do not match
+        #[arg(long)]
+        skip_checksum: bool,
+        /// Command to test each release, which exits with 0 if it is good, 125 if it
+        /// cannot be tested, and any other code if it is bad
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+}
+
+#[derive(clap::Args)]
+struct CacheArgs {
+    #[command(subcommand)]
+    command: CacheCommands,
+}
+
+#[derive(clap::Subcommand)]
+enum CacheCommands {
+    /// Print cached files
+    List,
+    /// Print the total size of cached files
+    Size,
+    /// Remove cached files
+    Clean {
+        /// Only remove files last modified before this long ago, such as `30d`
+        #[arg(long, value_parser = cache::parse_duration)]
+        older_than: Option<Duration>,
+    },
 }
 
 #[derive(clap::Args)]
@@ -51,196 +167,715 @@ struct AppArgs {
 
 #[derive(clap::Subcommand)]
 enum AppCommands {
+    /// Print Neovim configs in the config directory
+    List,
+    /// Create a config for a new NVIM_APPNAME
+    New {
+        name: String,
+        /// Directory to copy or Git repository to clone the config from
+        #[arg(long, value_name = "DIR|GIT_URL")]
+        from: Option<String>,
+    },
     /// Set NVIM_APPNAME
-    Use { name: String },
+    Use {
+        name: String,
+        /// Version to use whenever this NVIM_APPNAME is used
+        #[arg(long)]
+        version: Option<String>,
+        /// Stop using a specific version with this NVIM_APPNAME
+        #[arg(long, conflicts_with = "version")]
+        unbind: bool,
+    },
+    /// Stop setting NVIM_APPNAME to use the default config
+    Unset,
+    /// Copy the config of an NVIM_APPNAME into a new one
+    Clone { from: String, to: String },
+    /// Remove the config, data, state, and cache directories of an NVIM_APPNAME
+    Remove {
+        name: String,
+        /// Remove without asking for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
+    // Remove leftovers of an interrupted install before installing again. The lock
+    // is held until the command finishes.
+    let installs =
+        matches!(args.command, Commands::Get { .. } | Commands::Update { .. });
+    let _lock = if installs {
+        let lock = nrtm::lock_installs()?;
+        nrtm::clean_staging()?;
+        Some(lock)
+    } else {
+        None
+    };
+
     match &args.command {
         Commands::Restore => {
             shim::State::use_older_state()?;
         }
-        Commands::Get { version } => {
-            let releases = github::get_releases().await?;
-            let asset = releases
-                .iter()
-                .filter_map(|release| {
-                    if version == &release.tag_name {
-                        println!("Release found: {}", release.html_url);
-                        release.filter_assets()
-                    } else {
-                        None
-                    }
-                })
-                .next();
-
-            let Some(asset) = asset else {
-                anyhow::bail!("Failed to get a asset.");
-            };
-
-            let asset_type = asset.get_type().unwrap();
-            let download_target = CACHE_DIR.join(format!("{version}.{asset_type}"));
-            download_file(
-                &reqwest::Client::new(),
-                &asset.browser_download_url,
-                &download_target,
-            )
-            .await?;
-            extract_archive(&download_target, &asset_type, &NVIM_DIR.join(version))?;
-
-            eprintln!("Success to install Neovim {version}.");
+        Commands::Get {
+            versions,
+            skip_checksum,
+            from_source,
+            archive,
+            name,
+            build_type,
+            target,
+        } => {
+            if let Some(reference) = from_source {
+                install_from_source(reference, name.as_deref(), build_type).await?;
+            } else if let Some(archive) = archive {
+                install_archive(archive, name.as_deref())?;
+            } else {
+                let target = match target {
+                    Some(target) => *target,
+                    None => current_target()?,
+                };
+                install_releases(versions, *skip_checksum, &target).await?;
+            }
         }
         Commands::Remove { version } => {
             fs::remove_dir_all(NVIM_DIR.join(version))?;
+            let mut channel = nightly::Channel::read()?;
+            if channel.builds.contains(version) {
+                channel.remove(version);
+                channel.write()?;
+            }
             eprintln!("Success to remove Neovim {version}.");
         }
         Commands::Use { version } => {
             let mut state = shim::State::read().unwrap_or_default();
-            state.exe_path = if version == "system" {
-                shim::State::default().exe_path
-            } else {
-                Some(
-                    NVIM_DIR
-                        .join(format!("{version}/bin/nvim{EXE_SUFFIX}"))
-                        .display()
-                        .to_string(),
-                )
-            };
+            state.set_version(version)?;
             state.write()?;
+            if let Some(appname) = state.appname() {
+                match state.app_versions.get(&appname) {
+                    Some(bound) if Some(bound) != state.version.as_ref() => eprintln!(
+                        "Note: {appname} is bound to {bound}, which takes precedence. \
+                         Run `nrtm app use {appname} --unbind` to use {version}."
+                    ),
+                    _ => {}
+                }
+            }
+        }
+        Commands::Exec {
+            version,
+            appname,
+            args,
+        } => {
+            let mut state = shim::State::resolve(&env::current_dir()?)?;
+            state.set_version(version)?;
+            let mut command = match appname {
+                Some(appname) => state.command_with_appname(Some(appname))?,
+                None => state.command()?,
+            };
+
+            return Err(shim::exec(command.args(args)));
         }
         Commands::List => {
-            let exe_path = shim::State::read()
-                .unwrap_or_default()
-                .exe_path
-                .map(PathBuf::from);
-
-            for entry in NVIM_DIR.read_dir()? {
-                let Ok(entry) = entry else {
-                    continue;
-                };
+            let state = shim::State::resolve(&env::current_dir()?).unwrap_or_default();
+            let exe_path = state.exe_path.as_ref().map(PathBuf::from);
+            let appname = state.appname();
+            let nightly = nightly::Channel::read()?.current;
+
+            let mut any_used = false;
+            for name in nrtm::installed_versions()? {
                 let current_used = if let Some(ref exe_path) = exe_path {
-                    exe_path.starts_with(entry.path())
+                    exe_path.starts_with(NVIM_DIR.join(&name))
                 } else {
                     false
                 };
 
+                let mut notes = Vec::new();
+                if nightly.as_ref() == Some(&name) {
+                    notes.push("nightly".to_string());
+                }
+                if let Some(appname) = appname.as_ref().filter(|_| current_used) {
+                    notes.push(format!("app: {appname}"));
+                }
+                any_used |= current_used;
                 println!(
-                    "{: <2}{}",
+                    "{: <2}{}{}",
                     if current_used { "*" } else { "" },
-                    entry.file_name().to_str().unwrap(),
+                    name,
+                    if notes.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" ({})", notes.join(", "))
+                    },
                 );
             }
+            // Such as `system`, which is not listed above
+            if let Some(appname) = appname.filter(|_| !any_used) {
+                let version = state.version.as_deref().unwrap_or("system");
+                println!("* {version} (app: {appname})");
+            }
+        }
+        Commands::LsRemote { filter, json } => {
+            let releases = github::get_releases().await?;
+            let query = filter
+                .as_deref()
+                .map(str::parse::<version::VersionQuery>)
+                .transpose()?;
+            let releases: Vec<_> = match query {
+                None => releases.iter().collect(),
+                Some(version::VersionQuery::Req(req)) => releases
+                    .iter()
+                    .filter(|r| r.tag_name.starts_with('v'))
+                    .filter(|r| r.get_nvim_version().is_ok_and(|v| req.matches(&v)))
+                    .collect(),
+                Some(query) => query.select_release(&releases).into_iter().collect(),
+            };
+            print_releases(&releases, *json)?;
         }
         Commands::Which => {
             println!(
                 "{}",
-                shim::State::read()
+                shim::State::resolve(&env::current_dir()?)
                     .unwrap_or_default()
                     .exe_path
                     .unwrap_or_default()
             );
         }
         Commands::App(args) => match &args.command {
-            AppCommands::Use { name } => {
+            AppCommands::List => {
+                let current = shim::State::resolve(&env::current_dir()?)
+                    .unwrap_or_default()
+                    .appname();
+                let state = shim::State::read().unwrap_or_default();
+                for name in app::list()? {
+                    let current_used = current.as_ref() == Some(&name);
+                    println!(
+                        "{: <2}{name}{}",
+                        if current_used { "*" } else { "" },
+                        state
+                            .app_versions
+                            .get(&name)
+                            .map_or(String::new(), |v| format!(" ({v})")),
+                    );
+                }
+            }
+            AppCommands::New { name, from } => {
+                app::new(name, from.as_deref())?;
+                eprintln!("Success to create {name}.");
+            }
+            AppCommands::Use {
+                name,
+                version,
+                unbind,
+            } => {
+                app::validate_name(name)?;
+                if !app::exists(name)? {
+                    anyhow::bail!(
+                        "{name} has no config. Create it with `nrtm app new`."
+                    );
+                }
                 let mut state = shim::State::read().unwrap_or_default();
                 state.appname = Some(name.to_string());
+                if let Some(version) = version {
+                    // Recorded as resolved by `set_version`, such as `nightly`
+                    let mut selected = state.clone();
+                    selected.set_version(version)?;
+                    let version = selected.version.unwrap_or_default();
+                    eprintln!("{name} uses Neovim {version}.");
+                    state.app_versions.insert(name.to_string(), version);
+                } else if *unbind {
+                    state.app_versions.remove(name);
+                }
+                state.write()?;
+            }
+            AppCommands::Unset => {
+                let mut state = shim::State::read().unwrap_or_default();
+                state.appname = None;
+                state.write()?;
+            }
+            AppCommands::Clone { from, to } => {
+                app::clone(from, to)?;
+                eprintln!("Success to clone {from} to {to}.");
+            }
+            AppCommands::Remove { name, yes } => {
+                let dirs = app::dirs_to_remove(name)?;
+                if !yes && !dirs.is_empty() {
+                    // A config may have changes that are not pushed anywhere.
+                    for dir in &dirs {
+                        eprintln!("{}", dir.display());
+                    }
+                    if !confirm("Remove these directories?")? {
+                        anyhow::bail!("Canceled. Use --yes to remove without asking.");
+                    }
+                }
+                for dir in app::remove(name)? {
+                    eprintln!("Removed {}.", dir.display());
+                }
+                let mut state = shim::State::read().unwrap_or_default();
+                if state.appname.as_ref() == Some(name) {
+                    state.appname = None;
+                }
+                state.app_versions.remove(name);
                 state.write()?;
+                eprintln!("Success to remove {name}.");
             }
         },
-        Commands::Update => {
-            github::cache_response().await?;
+        Commands::Update { full, nightly } => {
+            github::cache_response(*full).await?;
+            if *nightly {
+                let releases = github::get_releases().await?;
+                let release = version::find_release(&releases, "nightly")?;
+                let name = release.install_name()?;
+                let progress = MultiProgress::new();
+                if NVIM_DIR.join(&name).exists() {
+                    eprintln!("Nightly {name} is already installed.");
+                    add_nightly(&name, &progress)?;
+                } else {
+                    install_release(release, false, &current_target()?, &progress)
+                        .await?;
+                }
+            }
             eprintln!("Success to update.");
         }
+        Commands::Rollback => {
+            let mut channel = nightly::Channel::read()?;
+            let Some(name) = channel.rollback() else {
+                anyhow::bail!("There is no older nightly build to roll back to.");
+            };
+            eprintln!("Nightly now refers to {name}.");
+            channel.write()?;
+            shim::State::refresh_nightly()?;
+        }
+        Commands::Link { name: None } => {
+            for name in shim::links()? {
+                println!("{name}");
+            }
+        }
+        Commands::Link { name: Some(name) } => {
+            shim::link(name)?;
+            eprintln!("Success to link {name}.");
+        }
+        Commands::Unlink { name } => {
+            shim::unlink(name)?;
+            eprintln!("Success to unlink {name}.");
+        }
+        Commands::Cache(args) => match &args.command {
+            CacheCommands::List => {
+                let now = SystemTime::now();
+                for entry in cache::entries()? {
+                    let age = now.duration_since(entry.modified).unwrap_or_default();
+                    println!(
+                        "{:>10}  {:>16} ago  {}",
+                        HumanBytes(entry.size).to_string(),
+                        HumanDuration(age).to_string(),
+                        entry.description,
+                    );
+                }
+            }
+            CacheCommands::Size => {
+                let size = cache::entries()?.iter().map(|e| e.size).sum();
+                println!("{}", HumanBytes(size));
+            }
+            CacheCommands::Clean { older_than } => {
+                let size = cache::clean(*older_than)?;
+                eprintln!("Removed {}.", HumanBytes(size));
+            }
+        },
+        Commands::Bisect {
+            good,
+            bad,
+            skip_checksum,
+            command,
+        } => {
+            bisect(good, bad, *skip_checksum, command).await?;
+        }
     }
 
     Ok(())
 }
 
-async fn download_file(
-    client: &reqwest::Client,
-    url: &str,
-    path: impl AsRef<Path>,
-) -> anyhow::Result<()> {
-    let res = client.get(url).send().await?;
-    let total_size = res.content_length().unwrap();
+/// Ask a yes/no question on the terminal, where no is the default
+fn confirm(prompt: &str) -> anyhow::Result<bool> {
+    eprint!("{prompt} [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn print_releases(releases: &[&github::Release], json: bool) -> anyhow::Result<()> {
+    #[derive(serde::Serialize)]
+    struct Row<'a> {
+        tag: &'a str,
+        version: Option<String>,
+        published_at: Option<&'a str>,
+        prerelease: bool,
+        asset: Option<&'a str>,
+    }
 
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("[{elapsed_precise}] [{wide_bar:.cyan/blue.dim}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")?
-        .progress_chars("━╸╌"));
+    let target = Target::current();
+    let rows = releases
+        .iter()
+        .map(|release| Row {
+            tag: &release.tag_name,
+            version: release.get_nvim_version().ok().map(|v| v.to_string()),
+            published_at: release.published_at.as_deref(),
+            prerelease: release.prerelease,
+            asset: target
+                .and_then(|target| release.filter_assets(&target))
+                .map(|a| a.name.as_str()),
+        })
+        .collect::<Vec<_>>();
 
-    let mut file = File::create(path)?;
-    let mut downloaded_size = 0;
-    let mut stream = res.bytes_stream();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
 
-    while let Some(item) = stream.next().await {
-        let chunk = item?;
-        file.write_all(&chunk)?;
-        downloaded_size += chunk.len();
-        pb.set_position(downloaded_size as u64);
+    let tag_width = rows.iter().map(|r| r.tag.len()).max().unwrap_or_default();
+    let version_width = rows
+        .iter()
+        .map(|r| r.version.as_ref().map_or(1, String::len))
+        .max()
+        .unwrap_or_default();
+    for row in rows {
+        println!(
+            "{:<tag_width$}  {:<version_width$}  {:<10}  {:<10}  {}",
+            row.tag,
+            row.version.as_deref().unwrap_or("-"),
+            row.published_at
+                .and_then(|date| date.get(..10))
+                .unwrap_or("-"),
+            if row.prerelease { "prerelease" } else { "" },
+            row.asset.unwrap_or("(no asset for this platform)"),
+        );
     }
 
     Ok(())
 }
 
-fn extract_archive(
-    archive: &PathBuf,
-    archive_type: &github::AssetType,
-    target: &PathBuf,
+async fn bisect(
+    good: &str,
+    bad: &str,
+    skip_checksum: bool,
+    command: &[String],
 ) -> anyhow::Result<()> {
-    fs::create_dir_all(target)?;
+    let releases = github::get_releases().await?;
+    let good = version::find_release(&releases, good)?;
+    let bad = version::find_release(&releases, bad)?;
+    let good_version = good.get_nvim_version()?;
+    let bad_version = bad.get_nvim_version()?;
+    if good_version >= bad_version {
+        anyhow::bail!(
+            "The good version {} must be older than the bad version {}.",
+            good.tag_name,
+            bad.tag_name,
+        );
+    }
+
+    let mut candidates = releases
+        .iter()
+        .filter(|r| r.tag_name.starts_with('v') && !r.prerelease)
+        .filter_map(|r| Some((r.get_nvim_version().ok()?, r)))
+        .filter(|(v, _)| &good_version < v && v < &bad_version)
+        .collect::<Vec<_>>();
+    candidates.sort_by(|(a, _), (b, _)| a.cmp(b));
+    candidates.insert(0, (good_version, good));
+    candidates.push((bad_version, bad));
+
+    let mut bisect = Bisect::new(candidates.into_iter().map(|(_, r)| r).collect())?;
+    while let Some(&release) = bisect.next() {
+        let name = &release.tag_name;
+        eprintln!(
+            "Testing {name} (roughly {} steps left)",
+            bisect.steps_left()
+        );
+        // Only while installing, since the command may install versions too
+        let lock = nrtm::lock_installs()?;
+        if !NVIM_DIR.join(name).exists() {
+            let progress = MultiProgress::new();
+            install_release(release, skip_checksum, &current_target()?, &progress)
+                .await?;
+        }
+        drop(lock);
 
-    fn strip_toplevel(rel_path: impl AsRef<Path>) -> anyhow::Result<PathBuf> {
-        let rel_path = rel_path.as_ref();
-        let prefix = rel_path.iter().next().unwrap();
-        let path = rel_path.strip_prefix(prefix)?.to_path_buf();
-        Ok(path)
+        let status = Command::new(&command[0])
+            .args(&command[1..])
+            .env(shim::VERSION_ENV, name)
+            .status()?;
+        let outcome = Outcome::from_exit_code(status.code());
+        eprintln!("{name} is {outcome:?}.");
+        bisect.mark(outcome);
     }
 
-    let archive = File::open(archive)?;
+    let first_bad = bisect.first_bad();
+    let skipped = bisect.skipped();
+    if !skipped.is_empty() {
+        println!(
+            "Only skipped releases are left to test. The first bad release after {} \
+             could be any of:",
+            bisect.last_good().tag_name,
+        );
+        for release in skipped.into_iter().chain([first_bad]) {
+            println!("{}", release.tag_name);
+        }
+        return Ok(());
+    }
+    println!(
+        "{} is the first bad release after {}.",
+        first_bad.tag_name,
+        bisect.last_good().tag_name,
+    );
+    println!("{}", first_bad.html_url);
 
-    match *archive_type {
-        github::AssetType::Zip => {
-            let mut archive = zip::ZipArchive::new(archive)?;
+    Ok(())
+}
 
-            for i in 0..archive.len() {
-                let mut file = archive.by_index(i)?;
-                let rel_path = strip_toplevel(file.mangled_name())?;
-                let path = target.join(rel_path);
+/// Install the releases matching `versions` concurrently
+async fn install_releases(
+    versions: &[String],
+    skip_checksum: bool,
+    target: &Target,
+) -> anyhow::Result<()> {
+    let all_releases = github::get_releases().await?;
+    let mut releases = Vec::new();
+    for version in versions {
+        let release = version::find_release(&all_releases, version)?;
+        // Such as `0.9` and `0.9.5`
+        if !releases
+            .iter()
+            .any(|r: &&github::Release| r.tag_name == release.tag_name)
+        {
+            releases.push(release);
+        }
+    }
 
-                if file.is_dir() {
-                    fs::create_dir_all(&path)?;
-                } else {
-                    fs::create_dir_all(path.parent().unwrap())?;
-                    let mut outfile = File::create(&path)?;
-                    io::copy(&mut file, &mut outfile)?;
+    let progress = MultiProgress::new();
+    let results = future::join_all(
+        releases
+            .iter()
+            .map(|release| install_release(release, skip_checksum, target, &progress)),
+    )
+    .await;
 
-                    #[cfg(unix)]
-                    if let Some(mode) = file.unix_mode() {
-                        fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
-                    }
-                }
-            }
+    if releases.len() == 1 {
+        return results.into_iter().next().unwrap();
+    }
+    let mut failed = 0;
+    for (release, result) in releases.iter().zip(results) {
+        if let Err(e) = result {
+            eprintln!("Failed to install {}: {e:#}", release.tag_name);
+            failed += 1;
         }
-        github::AssetType::TarGz => {
-            let tar = flate2::read::GzDecoder::new(archive);
-            let mut archive = tar::Archive::new(tar);
+    }
+    if failed > 0 {
+        anyhow::bail!("Failed to install {failed} of {} versions.", releases.len());
+    }
+    Ok(())
+}
 
-            for file in archive.entries()? {
-                let Ok(mut file) = file else {
-                    continue;
-                };
+fn current_target() -> anyhow::Result<Target> {
+    Target::current().context("Unsupported platform. Use --target to specify one.")
+}
+
+async fn install_release(
+    release: &github::Release,
+    skip_checksum: bool,
+    target: &Target,
+    progress: &MultiProgress,
+) -> anyhow::Result<()> {
+    // Messages are printed with the bars suspended, which are drawn for other
+    // versions when installing several in parallel.
+    progress.suspend(|| println!("Release found: {}", release.html_url));
 
-                let rel_path = strip_toplevel(file.path()?)?;
-                let path = target.join(&rel_path);
+    let native = Target::current() == Some(*target);
+    let mut name = release.install_name()?;
+    let Some(asset) = release.filter_assets(target) else {
+        anyhow::bail!(
+            "Failed to get a asset for {target}. Use --from-source {name} to build it."
+        );
+    };
+    // Builds for another platform are kept apart from the native one, which they
+    // would otherwise replace.
+    if !native {
+        name = format!("{name}-{target}");
+    }
 
-                file.unpack(&path)?;
+    let asset_type = asset.get_type().unwrap();
+    let url = &asset.browser_download_url;
+    let client = reqwest::Client::new();
+    // A checksum that cannot be fetched fails the install before downloading, or
+    // is warned about when verifying with --skip-checksum.
+    let expected = match fetch_checksum(&client, release, asset).await {
+        Err(e) if !skip_checksum => {
+            return Err(e.context("Use --skip-checksum to install anyway."));
+        }
+        expected => expected,
+    };
+
+    let download_target = cache::download_path(url, &asset.name);
+    let reusable = expected
+        .as_ref()
+        .is_ok_and(|sha256| cache::is_reusable(&download_target, sha256.as_deref()));
+    if reusable {
+        progress.suspend(|| eprintln!("Use the cached {}", download_target.display()));
+    } else {
+        fs::create_dir_all(download_target.parent().unwrap())?;
+        let config = Config::load()?.download;
+        download_file(&client, url, &download_target, &config, progress).await?;
+        cache::record(&download_target, url)?;
+    }
+
+    // Verifying and extracting are blocking, and run in parallel when installing
+    // several versions.
+    let asset_name = asset.name.clone();
+    let staging_name = name.clone();
+    // Builds for another platform cannot be run to check them.
+    let check = native;
+    let blocking_progress = progress.clone();
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        let progress = &blocking_progress;
+        if let Err(e) =
+            verify_checksum(&download_target, &asset_name, expected, progress)
+        {
+            if !skip_checksum {
+                fs::remove_file(&download_target)?;
+                return Err(e.context("Use --skip-checksum to install anyway."));
             }
+            progress.suspend(|| eprintln!("Warning: {e}"));
         }
+        let staging = nrtm::staging_dir(&staging_name)?;
+        archive::extract(&download_target, &asset_type, &staging, progress)?;
+        nrtm::commit_install(&staging, &staging_name, check)
+    })
+    .await??;
+    if release.tag_name == "nightly" && native {
+        add_nightly(&name, progress)?;
+    }
+
+    progress.suspend(|| eprintln!("Success to install Neovim {name}."));
+    Ok(())
+}
+
+/// Point the nightly channel at `name` and remove old builds
+fn add_nightly(name: &str, progress: &MultiProgress) -> anyhow::Result<()> {
+    let mut channel = nightly::Channel::read()?;
+    channel.add(name);
+    for build in channel.prune(Config::load()?.nightly.keep)? {
+        progress.suspend(|| eprintln!("Removed old nightly {build}."));
+    }
+    channel.write()?;
+    shim::State::refresh_nightly()?;
+    Ok(())
+}
+
+async fn install_from_source(
+    reference: &str,
+    name: Option<&str>,
+    build_type: &str,
+) -> anyhow::Result<()> {
+    let name = name.map_or_else(|| source::install_name(reference), String::from);
+    // The name is also used for the source directory in the cache.
+    nrtm::validate_install_name(&name)?;
+    let download_target = CACHE_DIR.join(format!("{name}-source.tar.gz"));
+    let config = Config::load()?;
+    let progress = MultiProgress::new();
+    // The tarball is served by the API, which rejects requests without a User-Agent.
+    download_file(
+        &github::new_client()?,
+        &source::tarball_url(&config.source, reference),
+        &download_target,
+        &config.download,
+        &progress,
+    )
+    .await?;
+
+    let source_dir = CACHE_DIR.join("src").join(&name);
+    if source_dir.exists() {
+        fs::remove_dir_all(&source_dir)?;
+    }
+    archive::extract(
+        &download_target,
+        &github::AssetType::TarGz,
+        &source_dir,
+        &progress,
+    )?;
+    let staging = nrtm::staging_dir(&name)?;
+    source::build(&source_dir, build_type, &staging)?;
+    nrtm::commit_install(&staging, &name, true)?;
+
+    eprintln!("Success to install Neovim {name}.");
+    Ok(())
+}
+
+fn install_archive(archive: &Path, name: Option<&str>) -> anyhow::Result<()> {
+    if let Some(name) = name {
+        nrtm::validate_install_name(name)?;
+    }
+    let asset_type = github::AssetType::detect(archive)?;
+
+    let staging = nrtm::staging_dir(name.unwrap_or("archive"))?;
+    archive::extract(archive, &asset_type, &staging, &MultiProgress::new())?;
+    let name = if let Some(name) = name {
+        name.to_string()
+    } else {
+        // The name is unknown until the archive is extracted.
+        let version = nvim_version(&staging.join(format!("bin/nvim{EXE_SUFFIX}")))
+            .context("Failed to get the version. Use --name to specify the name.")?;
+        format!("v{version}")
+    };
+    nrtm::commit_install(&staging, &name, true)?;
+
+    eprintln!("Success to install Neovim {name}.");
+    Ok(())
+}
+
+/// Fetch the published SHA-256 hash of `asset`, or `None` if it is not published
+async fn fetch_checksum(
+    client: &reqwest::Client,
+    release: &github::Release,
+    asset: &github::Asset,
+) -> anyhow::Result<Option<String>> {
+    let Some(checksum_asset) = release.checksum_asset(asset) else {
+        return Ok(None);
+    };
+
+    let content = client
+        .get(&checksum_asset.browser_download_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let hash = checksum::find_sha256(&content, &asset.name).with_context(|| {
+        format!(
+            "{} has no checksum for {}.",
+            checksum_asset.name, asset.name
+        )
+    })?;
+
+    Ok(Some(hash))
+}
+
+fn verify_checksum(
+    path: impl AsRef<Path>,
+    name: &str,
+    expected: anyhow::Result<Option<String>>,
+    progress: &MultiProgress,
+) -> anyhow::Result<()> {
+    let Some(expected) = expected? else {
+        progress.suspend(|| eprintln!("No checksum is published for {name}."));
+        return Ok(());
+    };
+
+    let actual = checksum::sha256_file(path)?;
+    if actual != expected {
+        anyhow::bail!(
+            "Checksum mismatch for {name}: expected {expected}, got {actual}."
+        );
     }
+    progress.suspend(|| eprintln!("Checksum verified: {actual}"));
 
     Ok(())
 }
@@ -0,0 +1,89 @@
+use std::{fs, path::PathBuf};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::{NVIM_DIR, STATE_DIR};
+
+static CHANNEL_FILE: Lazy<PathBuf> = Lazy::new(|| STATE_DIR.join("nightly.json"));
+
+/// Installed nightly builds, which are kept so that one can roll back to an older
+/// one when a nightly breaks.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Channel {
+    /// The build that `nightly` refers to
+    pub current: Option<String>,
+    /// Names of the installed builds, newest first
+    pub builds: Vec<String>,
+}
+
+impl Channel {
+    pub fn read() -> anyhow::Result<Channel> {
+        if !CHANNEL_FILE.exists() {
+            return Ok(Channel::default());
+        }
+        let channel = serde_json::from_str(&fs::read_to_string(&*CHANNEL_FILE)?)?;
+        Ok(channel)
+    }
+
+    pub fn write(&self) -> anyhow::Result<()> {
+        fs::write(&*CHANNEL_FILE, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Point the channel at `build`, which is the newest one
+    pub fn add(&mut self, build: &str) {
+        self.builds.retain(|b| b != build);
+        self.builds.insert(0, build.to_string());
+        self.current = Some(build.to_string());
+    }
+
+    /// Forget `build`, which has been removed
+    pub fn remove(&mut self, build: &str) {
+        self.builds.retain(|b| b != build);
+        if self.current.as_deref() == Some(build) {
+            self.current = self.builds.first().cloned();
+        }
+    }
+
+    /// Point the channel at the build before the current one
+    pub fn rollback(&mut self) -> Option<&str> {
+        let current = self.current.as_ref()?;
+        let i = self.builds.iter().position(|b| b == current)?;
+        self.current = Some(self.builds.get(i + 1)?.clone());
+        self.current.as_deref()
+    }
+
+    /// Remove builds beyond the newest `keep` ones, except the current one, returning
+    /// the removed ones
+    pub fn prune(&mut self, keep: usize) -> anyhow::Result<Vec<String>> {
+        let mut removed = Vec::new();
+        let mut kept = 0;
+        for build in self.builds.clone() {
+            if kept < keep || self.current.as_ref() == Some(&build) {
+                kept += 1;
+                continue;
+            }
+            let path = NVIM_DIR.join(&build);
+            if path.exists() {
+                fs::remove_dir_all(path)?;
+            }
+            self.builds.retain(|b| b != &build);
+            removed.push(build);
+        }
+        Ok(removed)
+    }
+}
+
+#[test]
+fn rollback_t() {
+    let mut channel = Channel::default();
+    channel.add("v0.10.0-dev+1");
+    channel.add("v0.10.0-dev+2");
+    channel.add("v0.10.0-dev+3");
+    assert_eq!(channel.rollback(), Some("v0.10.0-dev+2"));
+    assert_eq!(channel.rollback(), Some("v0.10.0-dev+1"));
+    assert_eq!(channel.rollback(), None);
+    channel.remove("v0.10.0-dev+1");
+    assert_eq!(channel.current.as_deref(), Some("v0.10.0-dev+3"));
+}
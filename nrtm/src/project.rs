@@ -0,0 +1,80 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use serde::Deserialize;
+
+/// Files searched for in the current directory and its ancestors, in order of
+/// priority
+static FILE_NAMES: [&str; 2] = [".nvim-version", "nrtm.toml"];
+
+/// Neovim version and NVIM_APPNAME pinned for a directory tree
+#[derive(Default, Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub version: Option<String>,
+    pub appname: Option<String>,
+}
+
+impl ProjectConfig {
+    /// Search `dir` and its ancestors for a project file, returning the path to the
+    /// nearest one with its content.
+    pub fn find(dir: &Path) -> anyhow::Result<Option<(PathBuf, ProjectConfig)>> {
+        for dir in dir.ancestors() {
+            for file_name in FILE_NAMES {
+                let path = dir.join(file_name);
+                if !path.is_file() {
+                    continue;
+                }
+                let config = Self::read(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                return Ok(Some((path, config)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn read(path: &Path) -> anyhow::Result<ProjectConfig> {
+        let content = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            Ok(toml::from_str(&content)?)
+        } else {
+            Ok(Self::parse_version_file(&content))
+        }
+    }
+
+    /// Parse a `.nvim-version` file, whose first line is a version and the optional
+    /// second line is NVIM_APPNAME.
+    fn parse_version_file(content: &str) -> ProjectConfig {
+        let mut lines = content
+            .lines()
+            .map(str::trim)
+            .map(|line| (!line.is_empty()).then(|| line.to_string()));
+        ProjectConfig {
+            version: lines.next().flatten(),
+            appname: lines.next().flatten(),
+        }
+    }
+}
+
+#[test]
+fn parse_version_file_t() {
+    let s = [
+        ("", None, None),
+        ("0.9\n", Some("0.9"), None),
+        ("nightly\nmyconfig\n", Some("nightly"), Some("myconfig")),
+        ("\nmyconfig", None, Some("myconfig")),
+    ];
+
+    for (content, version, appname) in s {
+        assert_eq!(
+            ProjectConfig::parse_version_file(content),
+            ProjectConfig {
+                version: version.map(String::from),
+                appname: appname.map(String::from),
+            }
+        );
+    }
+}
@@ -1,12 +1,41 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    env::{self, consts::EXE_SUFFIX},
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+use anyhow::Context as _;
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use which::which_all_global;
 
-use crate::{BIN_DIR, STATE_DIR};
+use crate::{
+    config::{Config, CONFIG_FILE},
+    nvim_exe,
+    project::ProjectConfig,
+    version, BIN_DIR, STATE_DIR,
+};
 
-static SYSTEM_NVIM: Lazy<Option<PathBuf>> = Lazy::new(|| {
-    let Ok(list) = which_all_global("nvim") else {
+static SYSTEM_NVIM: Lazy<Option<PathBuf>> = Lazy::new(|| which_global("nvim"));
+
+/// The shim executable, which other names are linked to
+static SHIM_EXE: Lazy<PathBuf> = Lazy::new(|| {
+    let path = BIN_DIR.join(format!("nvim{EXE_SUFFIX}"));
+    if path.exists() {
+        path
+    } else {
+        // Built by cargo without xtask
+        BIN_DIR.join(format!("shim{EXE_SUFFIX}"))
+    }
+});
+
+/// Find `name` in $PATH, skipping the shim and links to it
+fn which_global(name: impl AsRef<OsStr>) -> Option<PathBuf> {
+    let Ok(list) = which_all_global(name) else {
         return None;
     };
 
@@ -21,7 +50,13 @@ static SYSTEM_NVIM: Lazy<Option<PathBuf>> = Lazy::new(|| {
     }
 
     None
-});
+}
+
+/// Environment variable to override the version used by the shim
+pub const VERSION_ENV: &str = "NRTM_VERSION";
+
+/// Incremented when the state file changes incompatibly
+const FORMAT_VERSION: u32 = 1;
 
 enum StateKind {
     Draft,
@@ -39,43 +74,98 @@ impl StateKind {
     fn path(&self) -> PathBuf {
         use StateKind::*;
         match *self {
-            Draft => STATE_DIR.join("draft.shim"),
-            Current => STATE_DIR.join("current.shim"),
-            Old => STATE_DIR.join("old.shim"),
+            Draft => STATE_DIR.join("draft.json"),
+            Current => STATE_DIR.join("current.json"),
+            Old => STATE_DIR.join("old.json"),
         }
     }
 
-    fn read(&self) -> anyhow::Result<Option<String>> {
+    /// Path to the two-line state file written by older versions of nrtm
+    fn legacy_path(&self) -> PathBuf {
+        self.path().with_extension("shim")
+    }
+
+    fn read(&self) -> anyhow::Result<Option<State>> {
         let path = self.path();
         if !path.exists() {
+            return self.migrate();
+        }
+        let file: StateFile = serde_json::from_str(&fs::read_to_string(&path)?)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        if FORMAT_VERSION < file.format {
+            anyhow::bail!(
+                "{} is written by a newer version of nrtm (format {}).",
+                path.display(),
+                file.format,
+            );
+        }
+        Ok(Some(file.state))
+    }
+
+    /// Convert the legacy state file to the current format if it exists
+    fn migrate(&self) -> anyhow::Result<Option<State>> {
+        let legacy_path = self.legacy_path();
+        if !legacy_path.exists() {
             return Ok(None);
         }
-        let r = fs::read_to_string(path)?;
-        Ok(Some(r))
+        let state = State::parse_legacy(&fs::read_to_string(legacy_path)?);
+        self.write(&state)?;
+        Ok(Some(state))
     }
 
-    fn write(&self, content: String) -> anyhow::Result<()> {
-        fs::write(self.path(), content)?;
+    fn write(&self, state: &State) -> anyhow::Result<()> {
+        let file = StateFile {
+            format: FORMAT_VERSION,
+            state: state.clone(),
+        };
+        fs::write(self.path(), serde_json::to_string_pretty(&file)?)?;
+        let legacy_path = self.legacy_path();
+        if legacy_path.exists() {
+            fs::remove_file(legacy_path)?;
+        }
         Ok(())
     }
 
     fn replace_with(&self, kind: Self) -> anyhow::Result<()> {
-        self.write(kind.read()?.unwrap_or_default())?;
+        self.write(&kind.read()?.unwrap_or_default())?;
         Ok(())
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+struct StateFile {
+    format: u32,
+    #[serde(flatten)]
+    state: State,
+}
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct State {
+    /// Name of the selected version, such as `v0.9.5` or `system`
+    pub version: Option<String>,
     pub exe_path: Option<String>,
     pub appname: Option<String>,
+    /// Extra environment variables set by the shim
+    pub env: BTreeMap<String, String>,
+    /// Extra arguments passed before the ones given to the shim
+    pub args: Vec<String>,
+    /// Versions selected instead of `version` while an appname is used
+    pub app_versions: BTreeMap<String, String>,
+    /// Seconds since the Unix epoch when the state was written
+    pub updated_at: Option<u64>,
 }
 
 impl Default for State {
     fn default() -> Self {
         State {
+            version: None,
             exe_path: SYSTEM_NVIM.clone().map(|p| p.display().to_string()),
             appname: None,
+            env: BTreeMap::new(),
+            args: Vec::new(),
+            app_versions: BTreeMap::new(),
+            updated_at: None,
         }
     }
 }
@@ -87,33 +177,200 @@ impl State {
     }
 
     fn _read(kind: &StateKind) -> anyhow::Result<State> {
-        if let Some(content) = kind.read()? {
-            let Some((exe_path, appname)) = content.split_once('\n') else {
-                return Ok(Self::default());
-            };
-            let state = State {
-                exe_path: Some(exe_path.to_string()),
-                appname: Some(appname.to_string()),
-            };
+        if let Some(state) = kind.read()? {
             Ok(state)
         } else {
             let state = Self::default();
-            state._write(kind)?;
+            kind.write(&state)?;
             Ok(state)
         }
     }
 
-    pub fn write(&self) -> anyhow::Result<()> {
-        self._write(&StateKind::Draft)?;
+    /// Parse the content of a legacy state file, `exe_path\nappname`
+    fn parse_legacy(content: &str) -> State {
+        let Some((exe_path, appname)) = content.split_once('\n') else {
+            return Self::default();
+        };
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        State {
+            exe_path: non_empty(exe_path),
+            appname: non_empty(appname),
+            ..Self::default()
+        }
+    }
+
+    /// Read the state used in `dir`, which is overridden by the version bound to the
+    /// appname, the nearest project file, and then [`VERSION_ENV`].
+    pub fn resolve(dir: &Path) -> anyhow::Result<State> {
+        let mut state = Self::read()?;
+        let mut project_version = None;
+        if let Some((path, project)) = ProjectConfig::find(dir)? {
+            if let Some(version) = project.version {
+                project_version = Some((path.clone(), version));
+            }
+            if project.appname.is_some() {
+                state.appname = project.appname;
+            }
+        }
+        if let Some((path, version)) = project_version {
+            state
+                .set_version(&version)
+                .with_context(|| format!("Specified in {}", path.display()))?;
+        } else if let Some(appname) = state.appname() {
+            if let Some(version) = state.app_versions.get(&appname).cloned() {
+                state
+                    .set_version(&version)
+                    .with_context(|| format!("Bound to the app {appname}"))?;
+            }
+        }
+        if let Some(version) = env::var_os(VERSION_ENV) {
+            let version = version.to_string_lossy();
+            state
+                .set_version(&version)
+                .with_context(|| format!("Specified by ${VERSION_ENV}"))?;
+        }
+        Ok(state)
+    }
+
+    /// Select an installed version, or `system` for Neovim found in $PATH.
+    ///
+    /// `nightly` is recorded as is so that the state can follow the channel.
+    pub fn set_version(&mut self, version: &str) -> anyhow::Result<()> {
+        let name = if version == "system" {
+            self.exe_path = Self::default().exe_path;
+            version.to_string()
+        } else {
+            let name = version::find_installed(version)?;
+            self.exe_path = Some(nvim_exe(&name).display().to_string());
+            if version == "nightly" {
+                version.to_string()
+            } else {
+                name
+            }
+        };
+        self.version = Some(name);
         Ok(())
     }
 
-    fn _write(&self, kind: &StateKind) -> anyhow::Result<()> {
-        kind.write(format!(
-            "{}\n{}",
-            self.exe_path.clone().unwrap_or_default(),
-            self.appname.clone().unwrap_or_default(),
-        ))?;
+    /// Select the current nightly build again if the state follows the channel
+    pub fn refresh_nightly() -> anyhow::Result<()> {
+        let mut state = Self::read()?;
+        if state.version.as_deref() == Some("nightly") {
+            state.set_version("nightly")?;
+            state.write()?;
+        }
+        Ok(())
+    }
+
+    /// Select a version given as `+<version>` in the first argument of the shim.
+    ///
+    /// Returns `false` if the argument is not a version. Numbers such as `+10` are
+    /// always left to Neovim since they are line numbers. A version that is not
+    /// installed is an error rather than an Ex command for Neovim.
+    pub fn set_version_from_arg(&mut self, arg: &OsStr) -> anyhow::Result<bool> {
+        let Some(version) = arg.to_str().and_then(|a| a.strip_prefix('+')) else {
+            return Ok(false);
+        };
+        if version.is_empty() || version.bytes().all(|b| b.is_ascii_digit()) {
+            return Ok(false);
+        }
+        match self.set_version(version) {
+            Ok(()) => Ok(true),
+            Err(e) if version.parse::<version::VersionQuery>().is_ok() => Err(e),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// NVIM_APPNAME for Neovim, where the environment variable takes precedence
+    pub fn appname(&self) -> Option<String> {
+        env::var("NVIM_APPNAME")
+            .ok()
+            .or_else(|| self.appname.clone())
+    }
+
+    /// Build a command that launches the selected Neovim
+    pub fn command(&self) -> anyhow::Result<Command> {
+        self.command_with_appname(self.appname().as_deref())
+    }
+
+    /// Build a command that launches the selected Neovim with `appname` as
+    /// NVIM_APPNAME
+    pub fn command_with_appname(
+        &self,
+        appname: Option<&str>,
+    ) -> anyhow::Result<Command> {
+        let exe_path = self.exe_path.as_ref().context("Neovim is not installed.")?;
+        let config = Config::load()?;
+        let mut command = Command::new(exe_path);
+        self.apply_env(&mut command, &config, appname);
+        command.args(&self.args);
+        for profile in config.profiles(self.version.as_deref(), appname) {
+            command.args(&profile.args);
+        }
+
+        Ok(command)
+    }
+
+    /// Build a command that launches `tool` for the selected Neovim, such as a GUI.
+    ///
+    /// The tool is configured in `[tools.<tool>]` of the config, or otherwise found next
+    /// to `nvim` of the selected version.
+    pub fn tool_command(&self, tool: &str) -> anyhow::Result<Command> {
+        if tool == "nvim" {
+            return self.command();
+        }
+
+        let nvim = self.exe_path.as_ref().context("Neovim is not installed.")?;
+        let mut config = Config::load()?;
+        let appname = self.appname();
+        let tool_config = config.tools.remove(tool).unwrap_or_default();
+        let expand = |s: &str| s.replace("{nvim}", nvim);
+        let program = match &tool_config.path {
+            // A bare name is looked up in $PATH, where the link to the shim is skipped.
+            Some(path) if !path.contains(['/', std::path::MAIN_SEPARATOR]) => {
+                which_global(expand(path))
+                    .with_context(|| format!("{path} is not found in $PATH."))?
+            }
+            Some(path) => PathBuf::from(expand(path)),
+            None => {
+                let path = Path::new(nvim).with_file_name(format!("{tool}{EXE_SUFFIX}"));
+                if !path.exists() {
+                    anyhow::bail!(
+                        "{tool} is not found next to {nvim}. Configure it in [tools.{tool}] \
+                         of {}.",
+                        CONFIG_FILE.display()
+                    );
+                }
+                path
+            }
+        };
+
+        let mut command = Command::new(program);
+        self.apply_env(&mut command, &config, appname.as_deref());
+        command.envs(tool_config.env.iter().map(|(k, v)| (k, expand(v))));
+        command.args(tool_config.args.iter().map(|arg| expand(arg)));
+
+        Ok(command)
+    }
+
+    fn apply_env(&self, command: &mut Command, config: &Config, appname: Option<&str>) {
+        if let Some(appname) = appname {
+            command.env("NVIM_APPNAME", appname);
+        }
+
+        command.envs(&self.env);
+        for profile in config.profiles(self.version.as_deref(), appname) {
+            command.envs(&profile.env);
+        }
+    }
+
+    pub fn write(&self) -> anyhow::Result<()> {
+        let mut state = self.clone();
+        state.updated_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs());
+        StateKind::Draft.write(&state)?;
         Ok(())
     }
 
@@ -140,3 +397,119 @@ impl State {
         StateKind::Current.replace_with(StateKind::Draft)
     }
 }
+
+/// Name the shim is invoked as, such as `nvim` or `nvim-qt`
+pub fn invoked_name() -> String {
+    let arg0 = env::args_os().next().unwrap_or_default();
+    match Path::new(&arg0).file_stem().and_then(OsStr::to_str) {
+        Some("shim") | None => "nvim".into(),
+        Some(name) => name.into(),
+    }
+}
+
+fn link_path(tool: &str) -> anyhow::Result<PathBuf> {
+    if tool.is_empty() || tool.starts_with('.') || tool.contains(['/', '\\']) {
+        anyhow::bail!("Invalid name {tool:?} to link.");
+    }
+    // Executables of nrtm itself
+    if ["nvim", "nrtm", "shim"]
+        .iter()
+        .any(|name| tool.eq_ignore_ascii_case(name))
+    {
+        anyhow::bail!("{tool} cannot be linked or unlinked.");
+    }
+    Ok(BIN_DIR.join(format!("{tool}{EXE_SUFFIX}")))
+}
+
+/// Whether `path` is a link to the shim, or a copy of it where links are unavailable
+fn is_link(path: &Path) -> bool {
+    if let Ok(target) = fs::read_link(path) {
+        return target == *SHIM_EXE;
+    }
+    path != *SHIM_EXE
+        && fs::read(path).is_ok_and(|content| fs::read(&*SHIM_EXE).ok() == Some(content))
+}
+
+/// Make the shim available as `tool` in the bin directory
+pub fn link(tool: &str) -> anyhow::Result<()> {
+    let path = link_path(tool)?;
+    if path.exists() || path.is_symlink() {
+        anyhow::bail!("{} already exists.", path.display());
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&*SHIM_EXE, &path)?;
+    #[cfg(not(unix))]
+    if fs::hard_link(&*SHIM_EXE, &path).is_err() {
+        fs::copy(&*SHIM_EXE, &path)?;
+    }
+    Ok(())
+}
+
+/// Remove a link created by [`link`]
+pub fn unlink(tool: &str) -> anyhow::Result<()> {
+    let path = link_path(tool)?;
+    if !is_link(&path) {
+        anyhow::bail!("{} is not a link to the shim.", path.display());
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Names linked to the shim by [`link`]
+pub fn links() -> anyhow::Result<Vec<String>> {
+    let mut links = Vec::new();
+    for entry in BIN_DIR.read_dir()? {
+        let path = entry?.path();
+        if !is_link(&path) {
+            continue;
+        }
+        if let Some(name) = path.file_stem().and_then(OsStr::to_str) {
+            links.push(name.to_string());
+        }
+    }
+    links.sort();
+    Ok(links)
+}
+
+/// Run `command` in place of the current process, so that signals and job control
+/// such as Ctrl-Z reach Neovim directly. Returns only if it fails to start.
+///
+/// Where processes cannot be replaced, it runs as a child instead and the current
+/// process exits with its exit code.
+pub fn exec(command: &mut Command) -> anyhow::Error {
+    #[cfg(unix)]
+    let error = std::os::unix::process::CommandExt::exec(command);
+    #[cfg(not(unix))]
+    let error = match command.status() {
+        // A process terminated without an exit code failed.
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => e,
+    };
+
+    anyhow::Error::new(error).context(format!(
+        "Failed to run {}",
+        command.get_program().to_string_lossy()
+    ))
+}
+
+#[test]
+fn parse_legacy_t() {
+    let state = State::parse_legacy("/usr/bin/nvim\nfoo");
+    assert_eq!(state.exe_path.as_deref(), Some("/usr/bin/nvim"));
+    assert_eq!(state.appname.as_deref(), Some("foo"));
+    let state = State::parse_legacy("/usr/bin/nvim\n");
+    assert_eq!(state.exe_path.as_deref(), Some("/usr/bin/nvim"));
+    assert_eq!(state.appname, None);
+}
+
+#[test]
+fn link_path_t() {
+    for tool in ["nvim-qt", "vi", "nvim.appimage"] {
+        assert!(link_path(tool).is_ok(), "{tool}");
+    }
+    for tool in [
+        "", "..", "../foo", "a/b", "a\\b", ".hidden", "nvim", "nrtm", "Shim",
+    ] {
+        assert!(link_path(tool).is_err(), "{tool}");
+    }
+}
@@ -0,0 +1,72 @@
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::Context as _;
+
+use crate::config::SourceConfig;
+
+/// Build types accepted by Neovim's CMake configuration
+pub static BUILD_TYPES: [&str; 4] = ["Release", "RelWithDebInfo", "MinSizeRel", "Debug"];
+
+/// URL of the source tarball for a tag, commit, or branch
+pub fn tarball_url(source: &SourceConfig, reference: &str) -> String {
+    format!(
+        "{}/repos/{}/tarball/{reference}",
+        source.api_url.trim_end_matches('/'),
+        source.repo,
+    )
+}
+
+/// Name of the directory to install a build of `reference` into
+pub fn install_name(reference: &str) -> String {
+    reference.replace(['/', '\\'], "-")
+}
+
+/// Build Neovim in `source_dir` with CMake and install it into `prefix`
+pub fn build(source_dir: &Path, build_type: &str, prefix: &Path) -> anyhow::Result<()> {
+    which::which("cmake").context("CMake is required to build Neovim.")?;
+
+    // Releases before 0.9 keep bundled dependencies in `third-party`.
+    let deps_dir = if source_dir.join("cmake.deps").exists() {
+        "cmake.deps"
+    } else {
+        "third-party"
+    };
+    let build_type_def = format!("-DCMAKE_BUILD_TYPE={build_type}");
+    let prefix_def = format!("-DCMAKE_INSTALL_PREFIX={}", prefix.display());
+
+    cmake(
+        source_dir,
+        &["-S", deps_dir, "-B", ".deps", &build_type_def],
+    )?;
+    cmake(
+        source_dir,
+        &["--build", ".deps", "--config", build_type, "--parallel"],
+    )?;
+    cmake(
+        source_dir,
+        &["-S", ".", "-B", "build", &build_type_def, &prefix_def],
+    )?;
+    cmake(
+        source_dir,
+        &["--build", "build", "--config", build_type, "--parallel"],
+    )?;
+    cmake(source_dir, &["--install", "build", "--config", build_type])?;
+
+    Ok(())
+}
+
+fn cmake(dir: &Path, args: &[&str]) -> anyhow::Result<()> {
+    eprintln!("Run `cmake {}`", args.join(" "));
+    let status = Command::new("cmake")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .status()?;
+    if !status.success() {
+        anyhow::bail!("`cmake {}` failed with {status}.", args.join(" "));
+    }
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use std::{env::consts, fmt, str::FromStr};
+
+/// Platform that Neovim is installed for, such as `x86_64-linux`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Target {
+    pub arch: Arch,
+    pub os: Os,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arch {
+    X86,
+    X86_64,
+    Aarch64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Os {
+    Linux,
+    Macos,
+    Windows,
+}
+
+impl Target {
+    /// The platform nrtm is running on
+    pub fn current() -> Option<Target> {
+        format!("{}-{}", consts::ARCH, consts::OS).parse().ok()
+    }
+
+    /// Names of the assets for this target without extensions, most preferred first.
+    ///
+    /// Neovim has renamed its assets several times, so names of all releases are
+    /// listed.
+    pub fn asset_names(&self) -> &'static [&'static str] {
+        use Arch::*;
+        use Os::*;
+        match (self.os, self.arch) {
+            // Renamed from `nvim-linux64` and `nvim.appimage` in 0.10.4
+            (Linux, X86_64) => &["nvim-linux-x86_64", "nvim-linux64", "nvim"],
+            // Since 0.10.4
+            (Linux, Aarch64) => &["nvim-linux-arm64"],
+            (Linux, X86) => &[],
+            // Per architecture since 0.10, a universal or x86_64 one before
+            (Macos, X86_64) => &["nvim-macos-x86_64", "nvim-macos"],
+            // Rosetta runs x86_64 builds.
+            (Macos, Aarch64) => &["nvim-macos-arm64", "nvim-macos", "nvim-macos-x86_64"],
+            (Macos, X86) => &[],
+            (Windows, X86_64) => &["nvim-win64"],
+            // Windows on Arm runs x86_64 builds.
+            (Windows, Aarch64) => &["nvim-win-arm64", "nvim-win64"],
+            (Windows, X86) => &["nvim-win32"],
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    /// Parse `<arch>-<os>`, also accepting Rust target triples such as
+    /// `aarch64-apple-darwin`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (arch, rest) = s.split_once('-').ok_or_else(|| {
+            anyhow::anyhow!("Invalid target {s:?}, expected <arch>-<os>")
+        })?;
+        let arch = match arch {
+            "x86_64" | "amd64" | "x64" => Arch::X86_64,
+            "aarch64" | "arm64" => Arch::Aarch64,
+            "x86" | "i386" | "i586" | "i686" => Arch::X86,
+            _ => anyhow::bail!("Unknown architecture {arch:?}"),
+        };
+        let os = rest
+            .split('-')
+            .find_map(|part| match part {
+                "linux" => Some(Os::Linux),
+                "macos" | "darwin" | "apple" => Some(Os::Macos),
+                "windows" | "win" => Some(Os::Windows),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow::anyhow!("Unknown OS in {s:?}"))?;
+        Ok(Target { arch, os })
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let arch = match self.arch {
+            Arch::X86 => "x86",
+            Arch::X86_64 => "x86_64",
+            Arch::Aarch64 => "aarch64",
+        };
+        let os = match self.os {
+            Os::Linux => "linux",
+            Os::Macos => "macos",
+            Os::Windows => "windows",
+        };
+        write!(f, "{arch}-{os}")
+    }
+}
+
+#[test]
+fn filter_assets_t() {
+    use crate::github::{Asset, Release};
+
+    let release = |names: &[&str]| Release {
+        assets: names
+            .iter()
+            .map(|name| Asset {
+                name: name.to_string(),
+                content_type: "application/octet-stream".into(),
+                browser_download_url: String::new(),
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let v0_9 = release(&[
+        "nvim-linux64.tar.gz",
+        "nvim-linux64.tar.gz.sha256sum",
+        "nvim-macos.tar.gz",
+        "nvim-win64.zip",
+        "nvim.appimage",
+        "nvim.appimage.zsync",
+    ]);
+    let v0_11 = release(&[
+        "nvim-linux-arm64.appimage",
+        "nvim-linux-arm64.tar.gz",
+        "nvim-linux-x86_64.appimage",
+        "nvim-linux-x86_64.tar.gz",
+        "nvim-macos-arm64.tar.gz",
+        "nvim-macos-x86_64.tar.gz",
+        "nvim-win-arm64.zip",
+        "nvim-win64.zip",
+    ]);
+
+    let s = [
+        ("x86_64-linux", &v0_9, Some("nvim-linux64.tar.gz")),
+        ("aarch64-linux", &v0_9, None),
+        ("aarch64-apple-darwin", &v0_9, Some("nvim-macos.tar.gz")),
+        ("aarch64-windows", &v0_9, Some("nvim-win64.zip")),
+        ("x86-windows", &v0_9, None),
+        (
+            "x86_64-unknown-linux-gnu",
+            &v0_11,
+            Some("nvim-linux-x86_64.tar.gz"),
+        ),
+        ("arm64-linux", &v0_11, Some("nvim-linux-arm64.tar.gz")),
+        ("x86_64-macos", &v0_11, Some("nvim-macos-x86_64.tar.gz")),
+        ("aarch64-macos", &v0_11, Some("nvim-macos-arm64.tar.gz")),
+        (
+            "aarch64-pc-windows-msvc",
+            &v0_11,
+            Some("nvim-win-arm64.zip"),
+        ),
+    ];
+    for (target, release, name) in s {
+        let target: Target = target.parse().unwrap();
+        let asset = release.filter_assets(&target);
+        assert_eq!(asset.map(|a| a.name.as_str()), name, "{target}");
+    }
+}
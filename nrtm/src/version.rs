@@ -0,0 +1,154 @@
+use std::str::FromStr;
+
+use anyhow::Context as _;
+use semver::{Version, VersionReq};
+
+use crate::{github::Release, nightly};
+
+/// A version specified by the user, such as `0.9`, `^0.10`, `latest`, or `nightly`
+#[derive(Clone, Debug, PartialEq)]
+pub enum VersionQuery {
+    /// The newest non-prerelease version
+    Latest,
+    /// The release tagged as `stable`
+    Stable,
+    /// The release tagged as `nightly`
+    Nightly,
+    /// Versions matching a semver requirement
+    Req(VersionReq),
+}
+
+impl FromStr for VersionQuery {
+    type Err = semver::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use VersionQuery::*;
+        let query = match s {
+            "latest" => Latest,
+            "stable" => Stable,
+            "nightly" => Nightly,
+            _ => {
+                let s = s.strip_prefix('v').unwrap_or(s);
+                // A complete version means exactly that version, not `^x.y.z`.
+                if Version::parse(s).is_ok() {
+                    Req(VersionReq::parse(&format!("={s}"))?)
+                } else {
+                    Req(VersionReq::parse(s)?)
+                }
+            }
+        };
+        Ok(query)
+    }
+}
+
+impl VersionQuery {
+    pub fn select_release<'a>(&self, releases: &'a [Release]) -> Option<&'a Release> {
+        use VersionQuery::*;
+        match self {
+            Stable => releases.iter().find(|r| r.tag_name == "stable"),
+            Nightly => releases.iter().find(|r| r.tag_name == "nightly"),
+            Latest | Req(_) => releases
+                .iter()
+                .filter(|r| r.tag_name.starts_with('v'))
+                .filter_map(|r| Some((r.get_nvim_version().ok()?, r)))
+                .filter(|(v, r)| self.matches(v) && !r.prerelease)
+                .max_by(|(a, _), (b, _)| a.cmp(b))
+                .map(|(_, r)| r),
+        }
+    }
+
+    pub fn select_installed<'a>(&self, names: &'a [String]) -> Option<&'a String> {
+        use VersionQuery::*;
+        match self {
+            Stable => names.iter().find(|n| *n == "stable"),
+            Nightly => names.iter().find(|n| *n == "nightly"),
+            Latest | Req(_) => names
+                .iter()
+                .filter_map(|n| Some((parse_name(n)?, n)))
+                .filter(|(v, _)| self.matches(v))
+                .max_by(|(a, _), (b, _)| a.cmp(b))
+                .map(|(_, n)| n),
+        }
+    }
+
+    fn matches(&self, version: &Version) -> bool {
+        match self {
+            VersionQuery::Latest => version.pre.is_empty(),
+            VersionQuery::Req(req) => req.matches(version),
+            _ => false,
+        }
+    }
+}
+
+/// Parse a directory name such as `v0.9.5` as a version
+fn parse_name(name: &str) -> Option<Version> {
+    Version::parse(name.strip_prefix('v').unwrap_or(name)).ok()
+}
+
+/// Find the release whose tag is `version`, or the best match for it as a query
+pub fn find_release<'a>(
+    releases: &'a [Release],
+    version: &str,
+) -> anyhow::Result<&'a Release> {
+    if let Some(release) = releases.iter().find(|r| r.tag_name == version) {
+        return Ok(release);
+    }
+    let query: VersionQuery = version.parse()?;
+    query
+        .select_release(releases)
+        .with_context(|| format!("No release matches {version}."))
+}
+
+/// Find the installed version named `version`, or the best match for it as a query.
+///
+/// `nightly` refers to the current build of the nightly channel if there is one.
+pub fn find_installed(version: &str) -> anyhow::Result<String> {
+    let names = crate::installed_versions()?;
+    if version == "nightly" {
+        let channel = nightly::Channel::read()?;
+        if let Some(current) = channel.current.filter(|c| names.contains(c)) {
+            return Ok(current);
+        }
+    }
+    if names.iter().any(|n| n == version) {
+        return Ok(version.to_string());
+    }
+    let query: VersionQuery = version.parse()?;
+    query
+        .select_installed(&names)
+        .cloned()
+        .with_context(|| format!("No installed version matches {version}."))
+}
+
+#[test]
+fn select_release_t() {
+    let release = |tag: &str, prerelease| Release {
+        name: format!("Nvim {tag}"),
+        tag_name: tag.into(),
+        body: format!("NVIM {tag}"),
+        prerelease,
+        ..Default::default()
+    };
+    let releases = [
+        release("nightly", true),
+        release("stable", false),
+        release("v0.10.0", false),
+        release("v0.9.5", false),
+        release("v0.9.4", false),
+    ];
+
+    let s = [
+        ("latest", "v0.10.0"),
+        ("stable", "stable"),
+        ("nightly", "nightly"),
+        ("0.9", "v0.9.5"),
+        ("^0.10", "v0.10.0"),
+        ("0.9.4", "v0.9.4"),
+        ("v0.9.4", "v0.9.4"),
+    ];
+
+    for (query, tag) in s {
+        let query: VersionQuery = query.parse().unwrap();
+        assert_eq!(query.select_release(&releases).unwrap().tag_name, tag);
+    }
+}
@@ -131,7 +131,7 @@ fn build(dist: bool, cargo_opts: Vec<String>) -> anyhow::Result<PathBuf> {
             .args(&cargo_opts)
             .args(["--package", "nrtm-installer"]),
     )?;
-    let executable = executables.get(0).unwrap();
+    let executable = executables.first().unwrap();
 
     let copy_target = out_dir.join(format!("nrtm-installer{out_suffix}{EXE_SUFFIX}"));
     eprintln!("Copy {} to {}", executable.display(), copy_target.display());
@@ -205,9 +205,7 @@ fn get_executables(command: &mut Command) -> anyhow::Result<Vec<PathBuf>> {
 }
 
 fn get_build_target(cargo_opts: &[String]) -> Option<String> {
-    let Some(i) = cargo_opts.iter().position(|e| e.starts_with("--target")) else {
-        return None;
-    };
+    let i = cargo_opts.iter().position(|e| e.starts_with("--target"))?;
 
     if cargo_opts.get(i).unwrap() == "--target" {
         cargo_opts.get(i + 1).map(String::from)